@@ -1,29 +1,35 @@
-use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
-use ml_tracker::{InMemoryMetricStore, MetricLogger, MetricStore};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use ml_tracker::{InMemoryMetricStore, MetricLogger, MetricLoggerConfig, MetricStore};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::runtime::Runtime;
-use tokio::sync::Mutex;
 use uuid::Uuid;
 
+const POINTS_PER_WORKER: usize = 10_000;
+
+fn store() -> Arc<dyn MetricStore> {
+    Arc::new(InMemoryMetricStore::new().unwrap())
+}
+
 fn bench_metric_logging(c: &mut Criterion) {
     let rt = Runtime::new().unwrap();
-    let store: Arc<Mutex<dyn MetricStore>> =
-        Arc::new(Mutex::new(InMemoryMetricStore::new().unwrap()));
+    let store = store();
 
     let mut group = c.benchmark_group("metric_logging");
 
     for size in [100, 1000, 10000].iter() {
+        group.throughput(Throughput::Elements(*size as u64));
         group.bench_with_input(BenchmarkId::new("sequential", size), size, |b, &size| {
             b.iter(|| {
                 rt.block_on(async {
                     let run_id = Uuid::new_v4();
-                    let mut logger = MetricLogger::new(run_id, store.clone(), Default::default());
+                    let logger = MetricLogger::new(run_id, store.clone(), Default::default());
 
                     for i in 0..size {
                         logger.log("bench_metric", i as f64).await.unwrap();
                     }
 
-                    logger.flush().await.unwrap();
+                    logger.shutdown().await.unwrap();
                 })
             });
         });
@@ -32,5 +38,151 @@ fn bench_metric_logging(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, bench_metric_logging);
+/// Many workers, each with its own logger and background worker, writing
+/// to one store.
+fn bench_concurrent_loggers(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let store = store();
+
+    let mut group = c.benchmark_group("concurrent_loggers");
+
+    for workers in [1, 4, 16].iter() {
+        group.throughput(Throughput::Elements((workers * POINTS_PER_WORKER) as u64));
+        group.bench_with_input(
+            BenchmarkId::new("logger_per_worker", workers),
+            workers,
+            |b, &workers| {
+                b.iter(|| {
+                    rt.block_on(async {
+                        let tasks: Vec<_> = (0..workers)
+                            .map(|_| {
+                                let logger =
+                                    MetricLogger::new(Uuid::new_v4(), store.clone(), config());
+                                tokio::spawn(async move {
+                                    for i in 0..POINTS_PER_WORKER {
+                                        logger.log("bench_metric", i as f64).await.unwrap();
+                                    }
+                                    logger.shutdown().await.unwrap();
+                                })
+                            })
+                            .collect();
+                        for task in tasks {
+                            task.await.unwrap();
+                        }
+                    })
+                });
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("shared_logger", workers),
+            workers,
+            |b, &workers| {
+                b.iter(|| {
+                    rt.block_on(async {
+                        let logger = MetricLogger::new(Uuid::new_v4(), store.clone(), config());
+                        let tasks: Vec<_> = (0..workers)
+                            .map(|worker| {
+                                let logger = logger.clone();
+                                let name = format!("bench_metric_{worker}");
+                                tokio::spawn(async move {
+                                    for i in 0..POINTS_PER_WORKER {
+                                        logger.log(name.as_str(), i as f64).await.unwrap();
+                                    }
+                                })
+                            })
+                            .collect();
+                        for task in tasks {
+                            task.await.unwrap();
+                        }
+                        logger.shutdown().await.unwrap();
+                    })
+                });
+            },
+        );
+    }
+
+    group.finish();
+}
+
+/// Writers logging while readers repeatedly fetch the series being written.
+fn bench_read_while_write(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+
+    let mut group = c.benchmark_group("read_while_write");
+
+    for readers in [0, 1, 4].iter() {
+        let writers = 4;
+        group.throughput(Throughput::Elements((writers * POINTS_PER_WORKER) as u64));
+        group.bench_with_input(
+            BenchmarkId::new("readers", readers),
+            readers,
+            |b, &readers| {
+                b.iter(|| {
+                    rt.block_on(async {
+                        let store = store();
+                        let run_ids: Vec<_> = (0..writers).map(|_| Uuid::new_v4()).collect();
+                        let done = Arc::new(AtomicBool::new(false));
+
+                        let reader_tasks: Vec<_> = (0..readers)
+                            .map(|reader| {
+                                let store = store.clone();
+                                let run_id = run_ids[reader % writers];
+                                let done = done.clone();
+                                tokio::spawn(async move {
+                                    while !done.load(Ordering::Relaxed) {
+                                        store
+                                            .get_latest_metric(run_id, "bench_metric")
+                                            .await
+                                            .unwrap();
+                                        store.get_metrics(run_id, "bench_metric").await.unwrap();
+                                        tokio::task::yield_now().await;
+                                    }
+                                })
+                            })
+                            .collect();
+
+                        let writer_tasks: Vec<_> = run_ids
+                            .iter()
+                            .map(|&run_id| {
+                                let logger = MetricLogger::new(run_id, store.clone(), config());
+                                tokio::spawn(async move {
+                                    for i in 0..POINTS_PER_WORKER {
+                                        logger.log("bench_metric", i as f64).await.unwrap();
+                                    }
+                                    logger.shutdown().await.unwrap();
+                                })
+                            })
+                            .collect();
+
+                        for task in writer_tasks {
+                            task.await.unwrap();
+                        }
+                        done.store(true, Ordering::Relaxed);
+                        for task in reader_tasks {
+                            task.await.unwrap();
+                        }
+                    })
+                });
+            },
+        );
+    }
+
+    group.finish();
+}
+
+fn config() -> MetricLoggerConfig {
+    MetricLoggerConfig {
+        buffer_size: 4096,
+        queue_capacity: 4096,
+        ..MetricLoggerConfig::default()
+    }
+}
+
+criterion_group!(
+    benches,
+    bench_metric_logging,
+    bench_concurrent_loggers,
+    bench_read_while_write
+);
 criterion_main!(benches);
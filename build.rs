@@ -0,0 +1,4 @@
+fn main() {
+    // `sqlx::migrate!` embeds the migration files at compile time.
+    println!("cargo:rerun-if-changed=src/storage/database/migrations");
+}
@@ -1,39 +1,44 @@
-use ml_tracker::{Config, ExperimentTracker, Result, RunStatus};
+use ml_tracker::{Config, ExperimentTracker, Result};
 use std::time::Duration;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     println!("Starting basic tracking example...");
-    let tracker = ExperimentTracker::new(Config {
-        metric_buffer_size: 500,
-        metric_flush_interval: Duration::from_secs(30),
-        ..Config::default()
-    })
-    .await?;
+    let config = Config::load()?
+        .with_metric_buffer_size(500)
+        .with_metric_flush_interval(Duration::from_secs(30));
+    let tracker = ExperimentTracker::new(config).await?;
 
-    let mut experiment = tracker.create_experiment("mnist_training")?;
-    let mut run = experiment.start_run()?;
+    let mut experiment = tracker.create_experiment("mnist_training").await?;
+    let run = tracker.start_active_run(&mut experiment).await?;
     println!(
         "Created experiment 'mnist_training' with run ID: {}",
-        run.id
+        run.id()
     );
 
-    run.set_param("learning_rate", "0.01");
-    run.set_param("batch_size", "64");
+    run.log_param("learning_rate", 0.01).await?;
+    run.log_param("batch_size", 64).await?;
+    run.log_param(
+        "optimizer",
+        serde_json::json!({ "name": "sgd", "momentum": 0.9 }),
+    )
+    .await?;
 
     for epoch in 0..10 {
         let loss = 2.5 / (epoch + 1) as f64;
         let accuracy = 0.7 + (epoch as f64 * 0.02);
 
-        run.log_metric("loss", loss);
-        run.log_metric("accuracy", accuracy);
+        run.log_metric("loss", loss).await?;
+        run.log_metric("accuracy", accuracy).await?;
         println!(
             "Epoch {}: loss = {:.4}, accuracy = {:.4}",
             epoch, loss, accuracy
         );
     }
 
-    run.finish(RunStatus::Completed);
+    run.log_artifact("model", "model.txt", b"trained weights")
+        .await?;
+    run.finish().await?;
     println!("Training completed successfully!");
     Ok(())
 }
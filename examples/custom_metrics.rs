@@ -9,9 +9,9 @@ struct CustomMetrics {
 #[tokio::main]
 async fn main() -> Result<()> {
     println!("Starting custom metrics example...");
-    let tracker = ExperimentTracker::new(Config::default()).await?;
-    let mut experiment = tracker.create_experiment("custom_metrics_demo")?;
-    let mut run = experiment.start_run()?;
+    let tracker = ExperimentTracker::new(Config::load()?).await?;
+    let mut experiment = tracker.create_experiment("custom_metrics_demo").await?;
+    let mut run = tracker.start_run(&mut experiment).await?;
     println!(
         "Created experiment 'custom_metrics_demo' with run ID: {}",
         run.id
@@ -33,7 +33,7 @@ async fn main() -> Result<()> {
         tokio::time::sleep(Duration::from_secs(1)).await;
     }
 
-    run.finish(RunStatus::Completed);
+    tracker.finish_run(&mut run, RunStatus::Completed).await?;
     println!("Custom metrics tracking completed!");
     Ok(())
 }
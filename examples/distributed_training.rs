@@ -5,9 +5,9 @@ use tokio::sync::mpsc;
 #[tokio::main]
 async fn main() -> Result<()> {
     println!("Starting distributed training example...");
-    let tracker = ExperimentTracker::new(Config::default()).await?;
-    let mut experiment = tracker.create_experiment("distributed_training")?;
-    let mut run = experiment.start_run()?;
+    let tracker = ExperimentTracker::new(Config::load()?).await?;
+    let mut experiment = tracker.create_experiment("distributed_training").await?;
+    let mut run = tracker.start_run(&mut experiment).await?;
     println!(
         "Created experiment 'distributed_training' with run ID: {}",
         run.id
@@ -47,7 +47,7 @@ async fn main() -> Result<()> {
         handle.await.unwrap();
     }
 
-    run.finish(RunStatus::Completed);
+    tracker.finish_run(&mut run, RunStatus::Completed).await?;
     println!(
         "Distributed training completed! Total updates received: {}",
         total_updates
@@ -0,0 +1,474 @@
+use crate::artifacts::{Artifact, ArtifactManager};
+use crate::metrics::{MetricLogger, MetricLoggerConfig, MetricPoint, MetricStore};
+use crate::storage::{Database, Storage};
+use crate::{Result, Run, RunStatus, TrackerError};
+use chrono::Utc;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::any::Any;
+use std::cell::RefCell;
+use std::future::Future;
+use std::sync::{Arc, Once};
+use tokio::runtime::{Handle, RuntimeFlavor};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+/// A handle to a run in progress whose calls write straight through to the
+/// tracker's database, metric store and artifact storage.
+///
+/// Cloning the handle is cheap and every clone refers to the same run, so it
+/// can be shared with data loaders, evaluation tasks and so on. If the last
+/// clone is dropped before the run finishes, e.g. because training code
+/// returned early with `?` or panicked, the points buffered in
+/// [`ActiveRun::logger`] are written and the run is marked `Failed` before
+/// the drop returns, even while the runtime is shutting down. A panic
+/// message is recorded as the `panic` tag, as long as no panic hook set
+/// after the first run was started replaces the one that captures it. The
+/// error behind an early return is out of the drop's sight; use
+/// [`ActiveRun::run_guarded`] to record errors and interrupting signals as
+/// well.
+///
+/// ```ignore
+/// let run = tracker.start_active_run(&mut experiment).await?;
+/// run.log_params([("lr", "0.01")]).await?;
+/// run.log_metric("loss", 2.5).await?;
+/// run.log_artifact("model", "model.pt", &model_bytes).await?;
+/// run.finish().await?;
+/// ```
+#[derive(Clone)]
+pub struct ActiveRun {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    id: Uuid,
+    experiment_id: Uuid,
+    run: Mutex<Run>,
+    database: Arc<Database>,
+    store: Arc<dyn MetricStore>,
+    logger: MetricLogger,
+    artifacts: ArtifactManager,
+}
+
+impl ActiveRun {
+    pub(crate) fn new(
+        run: Run,
+        database: Arc<Database>,
+        store: Arc<dyn MetricStore>,
+        storage: Arc<Mutex<dyn Storage>>,
+        logger_config: MetricLoggerConfig,
+    ) -> Self {
+        capture_panic_messages();
+        let logger = MetricLogger::new(run.id, store.clone(), logger_config);
+        Self {
+            inner: Arc::new(Inner {
+                id: run.id,
+                experiment_id: run.experiment_id,
+                run: Mutex::new(run),
+                database,
+                store,
+                logger,
+                artifacts: ArtifactManager::new(storage),
+            }),
+        }
+    }
+
+    pub fn id(&self) -> Uuid {
+        self.inner.id
+    }
+
+    pub fn experiment_id(&self) -> Uuid {
+        self.inner.experiment_id
+    }
+
+    pub async fn status(&self) -> RunStatus {
+        self.inner.run.lock().await.status
+    }
+
+    /// A buffered logger for high-volume metrics, flushed whenever the run
+    /// finishes. Unlike [`ActiveRun::log_metric`], points only reach the
+    /// metric store once the buffer fills or is flushed.
+    pub fn logger(&self) -> MetricLogger {
+        self.inner.logger.clone()
+    }
+
+    /// Reads the run back from the database, including every metric logged
+    /// through this handle.
+    pub async fn snapshot(&self) -> Result<Run> {
+        self.inner
+            .database
+            .get_run_with_metrics(self.inner.id)
+            .await?
+            .ok_or_else(|| TrackerError::NotFound(format!("Run {}", self.inner.id)))
+    }
+
+    pub async fn log_metric(&self, name: impl Into<String>, value: f64) -> Result<()> {
+        self.store_metric(name.into(), value, None).await
+    }
+
+    pub async fn log_metric_at_step(
+        &self,
+        name: impl Into<String>,
+        value: f64,
+        step: u64,
+    ) -> Result<()> {
+        self.store_metric(name.into(), value, Some(step)).await
+    }
+
+    async fn store_metric(&self, name: String, value: f64, step: Option<u64>) -> Result<()> {
+        self.ensure_active().await?;
+
+        let point = MetricPoint {
+            run_id: self.inner.id,
+            name,
+            value,
+            timestamp: Utc::now(),
+            step,
+            epoch: None,
+        };
+        self.inner.store.store_metrics(&[point]).await
+    }
+
+    /// Logs a typed parameter; nested maps are flattened into dotted keys as
+    /// by [`Run::set_param`].
+    pub async fn log_param(&self, key: impl Into<String>, value: impl Into<Value>) -> Result<()> {
+        self.log_params([(key, value)]).await
+    }
+
+    pub async fn log_params<K, V>(&self, params: impl IntoIterator<Item = (K, V)>) -> Result<()>
+    where
+        K: Into<String>,
+        V: Into<Value>,
+    {
+        self.update(|run| {
+            for (key, value) in params {
+                run.set_param(key, value)?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Logs every field of a serializable config as a parameter, as by
+    /// [`Run::log_params_from`].
+    pub async fn log_params_from<T: Serialize>(&self, params: &T) -> Result<()> {
+        // Serialize up front so that `T` need not be `Sync`.
+        let params = serde_json::to_value(params)
+            .map_err(|e| TrackerError::InvalidOperation(e.to_string()))?;
+        self.update(|run| run.log_params_from(&params)).await
+    }
+
+    /// Rebuilds a config from the run's parameters, as by
+    /// [`Run::params_into`].
+    pub async fn params_into<T: DeserializeOwned>(&self) -> Result<T> {
+        self.inner.run.lock().await.params_into()
+    }
+
+    pub async fn set_tag(&self, key: impl Into<String>, value: impl Into<String>) -> Result<()> {
+        self.update(|run| {
+            run.add_tag(key, value);
+            Ok(())
+        })
+        .await
+    }
+
+    /// Stores `data` as `file_name` in the run's artifact storage and
+    /// records it on the run, along with its size and content hash. `name`
+    /// is the artifact's logical name, e.g. `"model"` for `"model.pt"`.
+    pub async fn log_artifact(
+        &self,
+        name: impl Into<String>,
+        file_name: &str,
+        data: &[u8],
+    ) -> Result<Artifact> {
+        self.ensure_active().await?;
+
+        let mut artifact = self
+            .inner
+            .artifacts
+            .store(self.inner.id, file_name, data)
+            .await?;
+        artifact.name = name.into();
+        self.inner.database.record_artifact(&artifact).await?;
+
+        let path = artifact.path.clone();
+        self.update(|run| {
+            run.add_artifact(path);
+            Ok(())
+        })
+        .await?;
+
+        Ok(artifact)
+    }
+
+    /// Marks the run `Completed`.
+    pub async fn finish(&self) -> Result<()> {
+        self.finish_with(RunStatus::Completed).await
+    }
+
+    /// Ends the run with a terminal `status` and releases it from its
+    /// experiment's active runs.
+    pub async fn finish_with(&self, status: RunStatus) -> Result<()> {
+        if !status.is_terminal() {
+            return Err(TrackerError::InvalidOperation(format!(
+                "{:?} is not a terminal run status",
+                status
+            )));
+        }
+
+        let mut run = self.inner.run.lock().await;
+        ensure_unfinished(&run)?;
+        self.inner.logger.flush().await?;
+
+        let previous = run.clone();
+        run.transition(status)?;
+        if let Err(e) = self.inner.database.update_run(&mut run).await {
+            *run = previous;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Runs `work` on its own task and finishes the run according to how it
+    /// ends:
+    ///
+    /// - `Ok`: the run is `Completed`.
+    /// - `Err`: the error is recorded as the `error` tag and the run is
+    ///   `Failed`. The error is returned.
+    /// - A panic: the panic message is recorded as the `panic` tag and the run
+    ///   is `Failed`, then the panic resumes.
+    /// - SIGINT or SIGTERM: the work is aborted, the signal is recorded as
+    ///   the `signal` tag and the run is `Interrupted`.
+    ///
+    /// The run's logger is flushed in every case. Note that once tokio has
+    /// installed its SIGINT/SIGTERM handlers they stay installed, so signals
+    /// arriving after the work ends no longer terminate the process by
+    /// default.
+    pub async fn run_guarded<F, Fut, T>(&self, work: F) -> Result<T>
+    where
+        F: FnOnce(ActiveRun) -> Fut,
+        Fut: Future<Output = Result<T>> + Send + 'static,
+        T: Send + 'static,
+    {
+        let mut task = tokio::spawn(work(self.clone()));
+
+        let joined = tokio::select! {
+            joined = &mut task => joined,
+            signal = shutdown_signal() => {
+                task.abort();
+                self.record_end(RunStatus::Interrupted, "signal", signal).await;
+                return Err(TrackerError::Interrupted(format!(
+                    "Run {} interrupted by {}",
+                    self.inner.id, signal
+                )));
+            }
+        };
+
+        match joined {
+            Ok(Ok(value)) => {
+                self.finish().await?;
+                Ok(value)
+            }
+            Ok(Err(e)) => {
+                self.record_end(RunStatus::Failed, "error", &e.to_string())
+                    .await;
+                Err(e)
+            }
+            Err(e) if e.is_panic() => {
+                let payload = e.into_panic();
+                self.record_end(RunStatus::Failed, "panic", &panic_message(&*payload))
+                    .await;
+                std::panic::resume_unwind(payload)
+            }
+            Err(e) => {
+                self.record_end(RunStatus::Interrupted, "error", &e.to_string())
+                    .await;
+                Err(TrackerError::Interrupted(e.to_string()))
+            }
+        }
+    }
+
+    /// Ends the run as by `fail_with`, only logging a failure to do so so
+    /// that how the work ended still reaches the caller.
+    async fn record_end(&self, status: RunStatus, tag: &str, reason: &str) {
+        if let Err(e) = self.fail_with(status, tag, reason).await {
+            tracing::warn!(run_id = %self.inner.id, error = %e, "failed to record how the run ended");
+        }
+    }
+
+    /// Records `reason` under `tag` and ends the run with `status`, unless
+    /// the work already finished it.
+    async fn fail_with(&self, status: RunStatus, tag: &str, reason: &str) -> Result<()> {
+        if self.status().await.is_terminal() {
+            return Ok(());
+        }
+        self.set_tag(tag, reason).await?;
+        self.finish_with(status).await
+    }
+
+    async fn ensure_active(&self) -> Result<()> {
+        ensure_unfinished(&*self.inner.run.lock().await)
+    }
+
+    /// Applies `change` to the run and saves it, leaving the in-memory run
+    /// untouched if saving fails.
+    async fn update(&self, change: impl FnOnce(&mut Run) -> Result<()>) -> Result<()> {
+        let mut run = self.inner.run.lock().await;
+        ensure_unfinished(&run)?;
+
+        let mut updated = run.clone();
+        change(&mut updated)?;
+        self.inner.database.update_run(&mut updated).await?;
+        *run = updated;
+        Ok(())
+    }
+}
+
+fn ensure_unfinished(run: &Run) -> Result<()> {
+    if run.status.is_terminal() {
+        return Err(TrackerError::InvalidOperation(format!(
+            "Run {} has already finished ({:?})",
+            run.id, run.status
+        )));
+    }
+    Ok(())
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        let run = self.run.get_mut();
+        if run.status.is_terminal() {
+            return;
+        }
+
+        let (tag, reason) = if std::thread::panicking() {
+            let message = PANIC_MESSAGE
+                .try_with(|message| message.borrow_mut().take())
+                .ok()
+                .flatten();
+            (
+                "panic",
+                message.unwrap_or_else(|| "panicked while the run was active".to_string()),
+            )
+        } else {
+            (
+                "error",
+                "run handle dropped before the run finished".to_string(),
+            )
+        };
+        let run_id = run.id;
+        let mut run = run.clone();
+        run.add_tag(tag, reason);
+        if let Err(e) = run.transition(RunStatus::Failed) {
+            tracing::warn!(run_id = %run_id, error = %e, "failed to mark dropped run as failed");
+            return;
+        }
+        let points = self.logger.take_unflushed();
+        let store = self.store.clone();
+        let database = self.database.clone();
+
+        // Everything has to be written before the drop returns: a task
+        // spawned on the current runtime never runs when the handle is
+        // dropped because `main` returned or the runtime is shutting down.
+        // The writes get a short-lived runtime of their own, since
+        // `block_on` is not allowed on a runtime thread.
+        let write = move || -> Result<()> {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(|e| TrackerError::Database(e.to_string()))?
+                .block_on(async move {
+                    if !points.is_empty() {
+                        if let Err(e) = store.store_metrics(&points).await {
+                            tracing::warn!(
+                                run_id = %run_id,
+                                error = %e,
+                                pending = points.len(),
+                                "failed to flush metrics of dropped run"
+                            );
+                        }
+                    }
+                    database.update_run(&mut run).await
+                })
+        };
+        // A multi-threaded runtime hands this worker's other tasks to
+        // another worker while the writes block it. A current-thread runtime
+        // has no other thread to give them to, so the writes run on a
+        // thread of their own instead.
+        let written = match Handle::try_current().map(|handle| handle.runtime_flavor()) {
+            Ok(RuntimeFlavor::MultiThread) => tokio::task::block_in_place(write),
+            Ok(_) => std::thread::spawn(write).join().unwrap_or_else(|_| {
+                Err(TrackerError::InvalidOperation(
+                    "panicked while marking dropped run as failed".to_string(),
+                ))
+            }),
+            Err(_) => write(),
+        };
+        if let Err(e) = written {
+            tracing::warn!(run_id = %run_id, error = %e, "failed to mark dropped run as failed");
+        }
+    }
+}
+
+thread_local! {
+    /// The message of the latest panic on this thread, for the drop of a
+    /// run that the panic unwinds through.
+    static PANIC_MESSAGE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Installs a panic hook that captures panic messages before handing the
+/// panic on to the hook it replaces.
+fn capture_panic_messages() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let message = panic_message(info.payload());
+            let _ = PANIC_MESSAGE.try_with(|latest| {
+                if let Ok(mut latest) = latest.try_borrow_mut() {
+                    *latest = Some(message);
+                }
+            });
+            previous(info);
+        }));
+    });
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "panicked with a non-string payload".to_string()
+    }
+}
+
+/// Resolves with the name of the first SIGINT or SIGTERM received. Never
+/// resolves if the handlers cannot be installed.
+async fn shutdown_signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let Ok(mut terminate) = signal(SignalKind::terminate()) else {
+            return std::future::pending().await;
+        };
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => match result {
+                Ok(()) => "SIGINT",
+                Err(_) => std::future::pending().await,
+            },
+            _ = terminate.recv() => "SIGTERM",
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        match tokio::signal::ctrl_c().await {
+            Ok(()) => "SIGINT",
+            Err(_) => std::future::pending().await,
+        }
+    }
+}
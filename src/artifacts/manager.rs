@@ -33,6 +33,7 @@ impl ArtifactManager {
             id: Uuid::new_v4(),
             run_id,
             name: name.to_string(),
+            file_name: name.to_string(),
             path,
             metadata,
         })
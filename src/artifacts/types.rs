@@ -7,6 +7,10 @@ pub struct Artifact {
     pub id: Uuid,
     pub run_id: Uuid,
     pub name: String,
+    /// Path relative to the run's artifacts, as passed to
+    /// [`ArtifactManager::store`](crate::ArtifactManager::store).
+    pub file_name: String,
+    /// Where the storage backend keeps the content.
     pub path: String,
     pub metadata: ArtifactMetadata,
 }
@@ -0,0 +1,158 @@
+use crate::metrics::{MetricRange, MetricStore};
+use crate::storage::Database;
+use crate::{Result, Run};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use uuid::Uuid;
+
+/// Whether lower or higher values of a metric are better.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum MetricGoal {
+    Minimize,
+    Maximize,
+}
+
+impl MetricGoal {
+    /// Guesses the goal from the metric's name: losses and errors (`loss`,
+    /// `val_err`, `mse`, `perplexity`, ...) are minimized, everything else
+    /// is maximized.
+    pub fn for_metric(name: &str) -> Self {
+        let name = name.to_ascii_lowercase();
+        if ["loss", "err", "mse", "mae", "perplexity"]
+            .iter()
+            .any(|marker| name.contains(marker))
+        {
+            MetricGoal::Minimize
+        } else {
+            MetricGoal::Maximize
+        }
+    }
+
+    fn better(self, a: f64, b: f64) -> bool {
+        match self {
+            MetricGoal::Minimize => a < b,
+            MetricGoal::Maximize => a > b,
+        }
+    }
+}
+
+/// One metric across the compared runs.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MetricComparison {
+    pub goal: MetricGoal,
+    /// Each run's last logged value.
+    pub last: Vec<Option<f64>>,
+    /// Each run's best value according to `goal`.
+    pub best: Vec<Option<f64>>,
+    /// Index of the run with the best value overall.
+    pub best_run: Option<usize>,
+}
+
+/// How a set of runs differ, built by
+/// [`ExperimentTracker::compare_runs`](crate::ExperimentTracker::compare_runs).
+///
+/// Every per-run `Vec` is in the order of `run_ids`, with `None` where a run
+/// lacks the entry. Params, tags and artifacts only list keys whose values
+/// are not the same in every run; metrics list every metric of any run.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RunComparison {
+    pub run_ids: Vec<Uuid>,
+    pub params: BTreeMap<String, Vec<Option<Value>>>,
+    pub tags: BTreeMap<String, Vec<Option<String>>>,
+    pub metrics: BTreeMap<String, MetricComparison>,
+    /// BLAKE3 content hashes recorded by
+    /// [`ActiveRun::log_artifact`](crate::ActiveRun::log_artifact), keyed by
+    /// the artifact's path within its run.
+    pub artifacts: BTreeMap<String, Vec<Option<String>>>,
+}
+
+impl RunComparison {
+    pub(crate) async fn collect(
+        runs: &[Run],
+        store: &dyn MetricStore,
+        database: &Database,
+    ) -> Result<Self> {
+        let mut metric_names = BTreeSet::new();
+        for run in runs {
+            metric_names.extend(store.list_metric_keys(run.id).await?);
+        }
+
+        let mut metrics = BTreeMap::new();
+        for name in metric_names {
+            let goal = MetricGoal::for_metric(&name);
+            let mut last = Vec::with_capacity(runs.len());
+            let mut best = Vec::with_capacity(runs.len());
+            for run in runs {
+                let summary = store
+                    .summarize_metric(run.id, &name, MetricRange::all())
+                    .await?;
+                last.push(summary.map(|summary| summary.last));
+                best.push(summary.map(|summary| match goal {
+                    MetricGoal::Minimize => summary.min,
+                    MetricGoal::Maximize => summary.max,
+                }));
+            }
+
+            let best_run = best
+                .iter()
+                .enumerate()
+                .filter_map(|(index, value)| Some((index, (*value)?)))
+                .reduce(|current, candidate| {
+                    if goal.better(candidate.1, current.1) {
+                        candidate
+                    } else {
+                        current
+                    }
+                })
+                .map(|(index, _)| index);
+
+            metrics.insert(
+                name,
+                MetricComparison {
+                    goal,
+                    last,
+                    best,
+                    best_run,
+                },
+            );
+        }
+
+        let mut artifacts = Vec::with_capacity(runs.len());
+        for run in runs {
+            let hashes: HashMap<String, String> = database
+                .list_artifacts(run.id)
+                .await?
+                .into_iter()
+                .map(|artifact| (artifact.file_name, artifact.metadata.content_hash))
+                .collect();
+            artifacts.push(hashes);
+        }
+
+        Ok(Self {
+            run_ids: runs.iter().map(|run| run.id).collect(),
+            params: differing(runs.iter().map(|run| &run.params)),
+            tags: differing(runs.iter().map(|run| &run.tags)),
+            metrics,
+            artifacts: differing(artifacts.iter()),
+        })
+    }
+}
+
+/// Keys whose values are not the same in every map, with each map's value.
+fn differing<'a, V>(
+    maps: impl Iterator<Item = &'a HashMap<String, V>> + Clone,
+) -> BTreeMap<String, Vec<Option<V>>>
+where
+    V: PartialEq + Clone + 'a,
+{
+    let keys: BTreeSet<&String> = maps.clone().flat_map(|map| map.keys()).collect();
+
+    keys.into_iter()
+        .filter_map(|key| {
+            let values: Vec<Option<V>> = maps.clone().map(|map| map.get(key).cloned()).collect();
+            let differs = values.windows(2).any(|pair| pair[0] != pair[1]);
+            differs.then(|| (key.clone(), values))
+        })
+        .collect()
+}
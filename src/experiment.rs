@@ -1,7 +1,7 @@
-use crate::{Result, Run, TrackerError};
+use crate::{Result, Run, RunStatus, TrackerError};
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{HashMap, HashSet};
 use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -12,7 +12,18 @@ pub struct Experiment {
     pub tags: HashMap<String, String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
-    pub active_run: Option<Uuid>,
+    /// The top-level runs that are `Running` or `Paused`. Experiments read
+    /// from the database take this from the runs' statuses; `start_run` and
+    /// `end_run` only update this copy.
+    #[serde(
+        default,
+        alias = "active_run",
+        deserialize_with = "deserialize_active_runs"
+    )]
+    pub active_runs: HashSet<Uuid>,
+    /// When set, at most one run may be active at a time.
+    #[serde(default)]
+    pub exclusive: bool,
 }
 
 impl Experiment {
@@ -25,7 +36,8 @@ impl Experiment {
             tags: HashMap::new(),
             created_at: now,
             updated_at: now,
-            active_run: None,
+            active_runs: HashSet::new(),
+            exclusive: false,
         }
     }
 
@@ -34,37 +46,69 @@ impl Experiment {
         self
     }
 
+    /// Restricts the experiment to a single active run, rejecting
+    /// `start_run` while another run is still active.
+    pub fn with_exclusive_runs(mut self) -> Self {
+        self.exclusive = true;
+        self
+    }
+
     pub fn add_tag(&mut self, key: impl Into<String>, value: impl Into<String>) {
         self.tags.insert(key.into(), value.into());
         self.updated_at = Utc::now();
     }
 
     pub fn start_run(&mut self) -> Result<Run> {
-        if self.active_run.is_some() {
+        if self.exclusive && !self.active_runs.is_empty() {
             return Err(TrackerError::InvalidOperation(
                 "An active run already exists".to_string(),
             ));
         }
 
         let run = Run::new(self.id);
-        self.active_run = Some(run.id);
+        self.active_runs.insert(run.id);
         self.updated_at = Utc::now();
         Ok(run)
     }
 
     pub fn end_run(&mut self, run_id: Uuid) -> Result<()> {
-        match self.active_run {
-            Some(active_id) if active_id == run_id => {
-                self.active_run = None;
-                self.updated_at = Utc::now();
-                Ok(())
-            }
-            Some(_) => Err(TrackerError::InvalidOperation(
-                "Run ID does not match active run".to_string(),
-            )),
-            None => Err(TrackerError::InvalidOperation(
-                "No active run to end".to_string(),
-            )),
+        if !self.active_runs.remove(&run_id) {
+            return Err(TrackerError::InvalidOperation(format!(
+                "Run {} is not active in this experiment",
+                run_id
+            )));
+        }
+
+        self.updated_at = Utc::now();
+        Ok(())
+    }
+
+    /// Finishes `run` with a terminal `status` and releases it from this
+    /// experiment's active runs.
+    pub fn finish_run(&mut self, run: &mut Run, status: RunStatus) -> Result<()> {
+        run.finish(status)?;
+        if self.active_runs.contains(&run.id) {
+            self.end_run(run.id)?;
         }
+        Ok(())
+    }
+}
+
+/// Accepts the single, optional `active_run` that experiments used to
+/// serialize as well as the current set of active runs.
+fn deserialize_active_runs<'de, D>(deserializer: D) -> std::result::Result<HashSet<Uuid>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ActiveRuns {
+        Many(HashSet<Uuid>),
+        One(Option<Uuid>),
     }
+
+    Ok(match ActiveRuns::deserialize(deserializer)? {
+        ActiveRuns::Many(runs) => runs,
+        ActiveRuns::One(run) => run.into_iter().collect(),
+    })
 }
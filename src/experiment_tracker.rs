@@ -1,38 +1,245 @@
-use crate::metrics::store::{InMemoryMetricStore, MetricStore};
-use crate::{Config, Experiment, Result};
+use crate::artifacts::{Artifact, ArtifactManager};
+use crate::comparison::RunComparison;
+use crate::metrics::store::MetricStore;
+use crate::metrics::{MetricLoggerConfig, SqliteMetricStore};
+use crate::storage::{Database, RunQuery, Storage};
+use crate::{ActiveRun, Config, Experiment, Result, Run, RunStatus, TrackerError};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use uuid::Uuid;
 
 pub struct ExperimentTracker {
     config: Config,
-    store: Arc<Mutex<dyn MetricStore>>,
+    database: Arc<Database>,
+    store: Arc<dyn MetricStore>,
+    storage: Arc<Mutex<dyn Storage>>,
+    artifacts: ArtifactManager,
 }
 
 impl ExperimentTracker {
     pub async fn new(config: Config) -> Result<Self> {
-        let store = Arc::new(Mutex::new(InMemoryMetricStore::new()?));
+        let backend = config.storage_backend()?;
+        let database = Arc::new(Database::from_config(&config).await?);
+        let store = Arc::new(SqliteMetricStore::new(&database));
+        let storage = backend.open().await?;
+        let artifacts = ArtifactManager::new(storage.clone());
 
-        Ok(Self { config, store })
+        Ok(Self {
+            config,
+            database,
+            store,
+            storage,
+            artifacts,
+        })
     }
 
-    pub fn create_experiment(&self, name: impl Into<String>) -> Result<Experiment> {
-        Ok(Experiment::new(name))
+    pub async fn create_experiment(&self, name: impl Into<String>) -> Result<Experiment> {
+        let experiment = Experiment::new(name);
+        self.database.create_experiment(&experiment).await?;
+        Ok(experiment)
     }
 
-    pub fn metric_store(&self) -> Arc<Mutex<dyn MetricStore>> {
+    /// Persists an experiment built with the `Experiment` builders, e.g. one
+    /// created with [`Experiment::with_exclusive_runs`].
+    pub async fn register_experiment(&self, experiment: Experiment) -> Result<Experiment> {
+        self.database.create_experiment(&experiment).await?;
+        Ok(experiment)
+    }
+
+    pub async fn save_experiment(&self, experiment: &Experiment) -> Result<()> {
+        self.database.update_experiment(experiment).await
+    }
+
+    /// Starts a new run on `experiment`, persists it and refreshes
+    /// `experiment.active_runs`. An exclusive experiment rejects the run
+    /// while another is active, including runs started by other processes.
+    pub async fn start_run(&self, experiment: &mut Experiment) -> Result<Run> {
+        self.start_prepared_run(experiment, Run::new(experiment.id))
+            .await
+    }
+
+    /// Starts `run`, created with `Run::new(experiment.id)` and given its
+    /// params and tags up front, like [`ExperimentTracker::start_run`]. The
+    /// run is stored together with its params, so a failure never leaves a
+    /// running run without them.
+    pub async fn start_prepared_run(
+        &self,
+        experiment: &mut Experiment,
+        mut run: Run,
+    ) -> Result<Run> {
+        if run.experiment_id != experiment.id || run.status != RunStatus::Running {
+            return Err(TrackerError::InvalidOperation(format!(
+                "Run {} is not a new run of experiment {}",
+                run.id, experiment.id
+            )));
+        }
+
+        self.database.start_run(&mut run).await?;
+        self.database
+            .load_active_runs(std::slice::from_mut(experiment))
+            .await?;
+        Ok(run)
+    }
+
+    /// Starts a run like [`ExperimentTracker::start_run`] and returns a
+    /// handle that writes metrics, params and artifacts straight through to
+    /// the tracker's stores.
+    pub async fn start_active_run(&self, experiment: &mut Experiment) -> Result<ActiveRun> {
+        let run = self.start_run(experiment).await?;
+        Ok(self.active_run(run))
+    }
+
+    /// Wraps an existing run, e.g. one recovered with
+    /// [`ExperimentTracker::get_active_run`], in an [`ActiveRun`] handle.
+    pub fn active_run(&self, run: Run) -> ActiveRun {
+        ActiveRun::new(
+            run,
+            self.database.clone(),
+            self.store.clone(),
+            self.storage.clone(),
+            MetricLoggerConfig {
+                buffer_size: self.config.metric_buffer_size,
+                flush_interval: self.config.metric_flush_interval,
+                ..MetricLoggerConfig::default()
+            },
+        )
+    }
+
+    /// Starts and persists a run nested under `parent`.
+    pub async fn start_child_run(&self, parent: &Run) -> Result<Run> {
+        let mut child = parent.start_child_run()?;
+        self.database.create_run(&mut child).await?;
+        Ok(child)
+    }
+
+    /// Saves the run's status, params, tags and artifacts, and appends the
+    /// metric points logged on it since it was last saved.
+    pub async fn save_run(&self, run: &mut Run) -> Result<()> {
+        self.database.update_run(run).await
+    }
+
+    /// Compares runs side by side: the params, tags and artifacts that
+    /// differ between them, and each metric's last and best values.
+    pub async fn compare_runs(&self, run_ids: &[Uuid]) -> Result<RunComparison> {
+        if run_ids.is_empty() {
+            return Err(TrackerError::InvalidOperation(
+                "no runs to compare".to_string(),
+            ));
+        }
+
+        let mut runs = Vec::with_capacity(run_ids.len());
+        for &run_id in run_ids {
+            let run = self
+                .database
+                .get_run(run_id)
+                .await?
+                .ok_or_else(|| TrackerError::NotFound(format!("Run {}", run_id)))?;
+            runs.push(run);
+        }
+
+        RunComparison::collect(&runs, self.store.as_ref(), &self.database).await
+    }
+
+    /// Lists the artifacts logged through [`ActiveRun::log_artifact`] for a
+    /// run, with the size and content hash recorded when they were stored.
+    /// Nothing is read from artifact storage.
+    pub async fn list_artifacts(&self, run_id: Uuid) -> Result<Vec<Artifact>> {
+        self.database.list_artifacts(run_id).await
+    }
+
+    /// Moves `run` to `status` and persists it. When the run reaches a
+    /// terminal state it is released as its experiment's active run.
+    pub async fn transition_run(&self, run: &mut Run, status: RunStatus) -> Result<()> {
+        run.transition(status)?;
+        self.database.update_run(run).await
+    }
+
+    pub async fn finish_run(&self, run: &mut Run, status: RunStatus) -> Result<()> {
+        if !status.is_terminal() {
+            return Err(TrackerError::InvalidOperation(format!(
+                "{:?} is not a terminal run status",
+                status
+            )));
+        }
+
+        self.transition_run(run, status).await
+    }
+
+    pub fn metric_store(&self) -> Arc<dyn MetricStore> {
         self.store.clone()
     }
 
+    /// The artifact storage backend selected by `Config::storage_backend`.
+    pub fn storage(&self) -> Arc<Mutex<dyn Storage>> {
+        self.storage.clone()
+    }
+
+    pub fn artifacts(&self) -> &ArtifactManager {
+        &self.artifacts
+    }
+
+    pub fn database(&self) -> Arc<Database> {
+        self.database.clone()
+    }
+
     pub fn get_config(&self) -> Config {
         self.config.clone()
     }
 
-    pub async fn get_experiment(&self, _id: Uuid) -> Result<Option<Experiment>> {
-        todo!("Implement experiment retrieval")
+    pub async fn get_experiment(&self, id: Uuid) -> Result<Option<Experiment>> {
+        self.database.get_experiment(id).await
+    }
+
+    pub async fn list_experiments(&self) -> Result<Vec<Experiment>> {
+        self.database.list_experiments().await
+    }
+
+    /// Reads a run's status, params, tags and artifacts. `Run::metrics` is
+    /// left empty; see [`ExperimentTracker::get_run_with_metrics`].
+    pub async fn get_run(&self, id: Uuid) -> Result<Option<Run>> {
+        self.database.get_run(id).await
+    }
+
+    /// Reads a run along with every point of every metric it logged.
+    pub async fn get_run_with_metrics(&self, id: Uuid) -> Result<Option<Run>> {
+        self.database.get_run_with_metrics(id).await
+    }
+
+    /// Fills `Run::metrics` of runs read with `get_run`, `list_runs` or
+    /// `search_runs`, with the `names` series or all of them when empty.
+    pub async fn load_run_metrics(&self, runs: &mut [Run], names: &[&str]) -> Result<()> {
+        self.database.load_run_metrics(runs, names).await
+    }
+
+    pub async fn get_active_run(&self, experiment: &mut Experiment) -> Result<Option<Run>> {
+        experiment.get_active_run(&self.database).await
+    }
+
+    /// Lists the active runs of an experiment, most recently started first.
+    pub async fn list_active_runs(&self, experiment_id: Uuid) -> Result<Vec<Run>> {
+        self.database.list_active_runs(experiment_id).await
+    }
+
+    pub async fn list_runs(&self, experiment_id: Uuid) -> Result<Vec<Run>> {
+        self.database.list_runs(experiment_id).await
+    }
+
+    pub async fn list_child_runs(&self, run_id: Uuid) -> Result<Vec<Run>> {
+        self.database.list_child_runs(run_id).await
+    }
+
+    pub async fn list_descendant_runs(&self, run_id: Uuid) -> Result<Vec<Run>> {
+        self.database.list_descendant_runs(run_id).await
+    }
+
+    /// Finds runs across all experiments with a [`RunQuery`], e.g.
+    /// `RunQuery::new().with_filter("metrics.val_loss < 0.3 and status = Completed")`.
+    pub async fn search_runs(&self, query: &RunQuery) -> Result<Vec<Run>> {
+        self.database.search_runs(query).await
     }
 
     pub async fn shutdown(&self) -> Result<()> {
+        self.database.close().await;
         Ok(())
     }
 }
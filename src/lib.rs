@@ -1,8 +1,12 @@
-use std::path::PathBuf;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::time::Duration;
 use thiserror::Error;
 
+pub mod active_run;
 pub mod artifacts;
+pub mod comparison;
 pub mod experiment;
 pub mod experiment_tracker;
 pub mod metrics;
@@ -10,14 +14,19 @@ pub mod run;
 pub mod storage;
 pub mod ui;
 
+pub use active_run::ActiveRun;
 pub use artifacts::{Artifact, ArtifactManager, ArtifactMetadata};
+pub use comparison::{MetricComparison, MetricGoal, RunComparison};
 pub use experiment::Experiment;
 pub use experiment_tracker::ExperimentTracker;
 pub use metrics::{
-    InMemoryMetricStore, MetricLogger, MetricLoggerConfig, MetricPoint, MetricStore, SystemMetrics,
+    Downsample, InMemoryMetricStore, MetricLogger, MetricLoggerConfig, MetricPoint, MetricRange,
+    MetricStore, MetricSummary, SqliteMetricStore, SystemMetrics,
+};
+pub use run::{Run, RunStatus, RunTransition};
+pub use storage::{
+    Database, InMemoryStorage, LocalStorage, RunQuery, S3Storage, Storage, StorageBackend,
 };
-pub use run::{Run, RunStatus};
-pub use storage::{Database, LocalStorage, S3Storage, Storage};
 
 #[derive(Error, Debug)]
 pub enum TrackerError {
@@ -29,6 +38,14 @@ pub enum TrackerError {
     InvalidOperation(String),
     #[error("Not found: {0}")]
     NotFound(String),
+    #[error("Invalid query: {0}")]
+    InvalidQuery(String),
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+    #[error("Interrupted: {0}")]
+    Interrupted(String),
+    #[error("Param conflict: {0}")]
+    ParamConflict(String),
 }
 
 pub type Result<T> = std::result::Result<T, TrackerError>;
@@ -36,6 +53,9 @@ pub type Result<T> = std::result::Result<T, TrackerError>;
 #[derive(Debug, Clone)]
 pub struct Config {
     pub storage_path: PathBuf,
+    /// Artifact storage backend URI, e.g. `s3://bucket/prefix` or
+    /// `memory://`. When unset, artifacts are stored under `storage_path`.
+    pub storage_uri: Option<String>,
     pub database_url: String,
     pub db_pool_size: u32,
     pub db_connection_timeout: Duration,
@@ -47,6 +67,7 @@ impl Default for Config {
     fn default() -> Self {
         Self {
             storage_path: PathBuf::from("./mltracker"),
+            storage_uri: None,
             database_url: "sqlite:experiments.db".to_string(),
             db_pool_size: 5,
             db_connection_timeout: Duration::from_secs(30),
@@ -56,10 +77,183 @@ impl Default for Config {
     }
 }
 
+impl Config {
+    pub fn with_storage_path(mut self, storage_path: impl Into<PathBuf>) -> Self {
+        self.storage_path = storage_path.into();
+        self
+    }
+
+    pub fn with_storage_uri(mut self, storage_uri: impl Into<String>) -> Self {
+        self.storage_uri = Some(storage_uri.into());
+        self
+    }
+
+    /// Resolves where artifacts are stored: `storage_uri` when set,
+    /// otherwise a local directory at `storage_path`.
+    pub fn storage_backend(&self) -> Result<StorageBackend> {
+        match &self.storage_uri {
+            Some(uri) => uri.parse(),
+            None => Ok(StorageBackend::Local(self.storage_path.clone())),
+        }
+    }
+
+    pub fn with_database_url(mut self, database_url: impl Into<String>) -> Self {
+        self.database_url = database_url.into();
+        self
+    }
+
+    pub fn with_db_pool_size(mut self, db_pool_size: u32) -> Self {
+        self.db_pool_size = db_pool_size;
+        self
+    }
+
+    pub fn with_db_connection_timeout(mut self, timeout: Duration) -> Self {
+        self.db_connection_timeout = timeout;
+        self
+    }
+
+    pub fn with_metric_buffer_size(mut self, metric_buffer_size: usize) -> Self {
+        self.metric_buffer_size = metric_buffer_size;
+        self
+    }
+
+    pub fn with_metric_flush_interval(mut self, interval: Duration) -> Self {
+        self.metric_flush_interval = interval;
+        self
+    }
+
+    /// Builds the configuration from every source, each overriding the one
+    /// before it: defaults, the TOML file named by `ML_TRACKER_CONFIG` (if
+    /// set), then individual `ML_TRACKER_*` variables.
+    pub fn load() -> Result<Self> {
+        let config = Config::default();
+        let config = match env_var::<PathBuf>("ML_TRACKER_CONFIG")? {
+            Some(path) => config.merge_file(path)?,
+            None => config,
+        };
+        config.merge_env()
+    }
+
+    /// Loads a TOML config file over the defaults. See [`Config::merge_file`].
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Config::default().merge_file(path)
+    }
+
+    /// Reads `ML_TRACKER_*` variables over the defaults. See
+    /// [`Config::merge_env`].
+    pub fn from_env() -> Result<Self> {
+        Config::default().merge_env()
+    }
+
+    /// Overrides the keys set in a TOML config file; durations are given in
+    /// seconds:
+    ///
+    /// ```toml
+    /// storage_path = "/data/mltracker"
+    /// database_url = "sqlite:/data/experiments.db"
+    /// db_connection_timeout_secs = 10
+    /// ```
+    pub fn merge_file(self, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| TrackerError::InvalidConfig(format!("{}: {}", path.display(), e)))?;
+        let layer: ConfigLayer = toml::from_str(&contents)
+            .map_err(|e| TrackerError::InvalidConfig(format!("{}: {}", path.display(), e)))?;
+
+        Ok(layer.apply_to(self))
+    }
+
+    /// Overrides the keys set in the environment: `ML_TRACKER_STORAGE_PATH`,
+    /// `ML_TRACKER_STORAGE_URI`, `ML_TRACKER_DATABASE_URL`, `ML_TRACKER_DB_POOL_SIZE`,
+    /// `ML_TRACKER_DB_CONNECTION_TIMEOUT_SECS`, `ML_TRACKER_METRIC_BUFFER_SIZE`
+    /// and `ML_TRACKER_METRIC_FLUSH_INTERVAL_SECS`.
+    pub fn merge_env(self) -> Result<Self> {
+        let layer = ConfigLayer {
+            storage_path: env_var("ML_TRACKER_STORAGE_PATH")?,
+            storage_uri: env_var("ML_TRACKER_STORAGE_URI")?,
+            database_url: env_var("ML_TRACKER_DATABASE_URL")?,
+            db_pool_size: env_var("ML_TRACKER_DB_POOL_SIZE")?,
+            db_connection_timeout_secs: env_var("ML_TRACKER_DB_CONNECTION_TIMEOUT_SECS")?,
+            metric_buffer_size: env_var("ML_TRACKER_METRIC_BUFFER_SIZE")?,
+            metric_flush_interval_secs: env_var("ML_TRACKER_METRIC_FLUSH_INTERVAL_SECS")?,
+        };
+
+        Ok(layer.apply_to(self))
+    }
+}
+
+/// The subset of `Config` set by one source.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigLayer {
+    storage_path: Option<PathBuf>,
+    storage_uri: Option<String>,
+    database_url: Option<String>,
+    db_pool_size: Option<u32>,
+    db_connection_timeout_secs: Option<u64>,
+    metric_buffer_size: Option<usize>,
+    metric_flush_interval_secs: Option<u64>,
+}
+
+impl ConfigLayer {
+    fn apply_to(self, mut config: Config) -> Config {
+        if let Some(storage_path) = self.storage_path {
+            config.storage_path = storage_path;
+        }
+        if let Some(storage_uri) = self.storage_uri {
+            config.storage_uri = Some(storage_uri);
+        }
+        if let Some(database_url) = self.database_url {
+            config.database_url = database_url;
+        }
+        if let Some(db_pool_size) = self.db_pool_size {
+            config.db_pool_size = db_pool_size;
+        }
+        if let Some(secs) = self.db_connection_timeout_secs {
+            config.db_connection_timeout = Duration::from_secs(secs);
+        }
+        if let Some(metric_buffer_size) = self.metric_buffer_size {
+            config.metric_buffer_size = metric_buffer_size;
+        }
+        if let Some(secs) = self.metric_flush_interval_secs {
+            config.metric_flush_interval = Duration::from_secs(secs);
+        }
+        config
+    }
+}
+
+fn env_var<T>(name: &str) -> Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    match std::env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|e| TrackerError::InvalidConfig(format!("{}={}: {}", name, value, e))),
+        Err(std::env::VarError::NotPresent) => Ok(None),
+        Err(e) => Err(TrackerError::InvalidConfig(format!("{}: {}", name, e))),
+    }
+}
+
 impl Experiment {
-    pub fn get_active_run(&mut self) -> Result<Option<Run>> {
-        // Query for any run that hasn't been marked as completed
-        // Implementation details will depend on your storage backend
-        todo!("Implement active run retrieval logic")
+    /// Resolves the experiment's active runs from persisted state.
+    ///
+    /// Every `Running` or `Paused` run of this experiment is returned, newest
+    /// first, and `active_runs` is refreshed to match: runs that have since
+    /// finished are dropped and runs left behind by a crashed process are
+    /// included, so a job can resume or close out whatever a previous
+    /// process left behind.
+    pub async fn get_active_runs(&mut self, database: &Database) -> Result<Vec<Run>> {
+        let runs = database.list_active_runs(self.id).await?;
+        self.active_runs = runs.iter().map(|run| run.id).collect();
+        Ok(runs)
+    }
+
+    /// Returns the most recently started of the experiment's active runs.
+    /// See [`Experiment::get_active_runs`].
+    pub async fn get_active_run(&mut self, database: &Database) -> Result<Option<Run>> {
+        Ok(self.get_active_runs(database).await?.into_iter().next())
     }
 }
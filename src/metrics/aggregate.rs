@@ -0,0 +1,254 @@
+use crate::metrics::store::MetricPoint;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::borrow::Borrow;
+use std::ops::{Bound, RangeBounds};
+
+/// The part of a series a query covers, and the axis its points are ordered
+/// along.
+///
+/// A step range only covers points logged with a step and orders them by
+/// step; a time range covers every point and orders them by timestamp.
+/// Bounds follow the usual range syntax:
+///
+/// ```ignore
+/// MetricRange::steps(1000..2000);
+/// MetricRange::steps(..);                      // every point with a step
+/// MetricRange::time(started..);
+/// MetricRange::all();                          // same as MetricRange::time(..)
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricRange {
+    Steps(Bound<u64>, Bound<u64>),
+    Time(Bound<DateTime<Utc>>, Bound<DateTime<Utc>>),
+}
+
+impl MetricRange {
+    pub fn all() -> Self {
+        Self::Time(Bound::Unbounded, Bound::Unbounded)
+    }
+
+    pub fn steps(range: impl RangeBounds<u64>) -> Self {
+        Self::Steps(range.start_bound().cloned(), range.end_bound().cloned())
+    }
+
+    pub fn time(range: impl RangeBounds<DateTime<Utc>>) -> Self {
+        Self::Time(range.start_bound().cloned(), range.end_bound().cloned())
+    }
+
+    pub fn contains(&self, point: &MetricPoint) -> bool {
+        match self {
+            Self::Steps(start, end) => point
+                .step
+                .is_some_and(|step| (*start, *end).contains(&step)),
+            Self::Time(start, end) => (*start, *end).contains(&point.timestamp),
+        }
+    }
+
+    /// Sorts `points` along the range's axis, keeping points at the same
+    /// position in the order they were logged.
+    pub(crate) fn sort<P: Borrow<MetricPoint>>(&self, points: &mut [P]) {
+        match self {
+            Self::Steps(..) => points.sort_by_key(|point| point.borrow().step),
+            Self::Time(..) => points.sort_by_key(|point| point.borrow().timestamp),
+        }
+    }
+
+    /// The point's position along the range's axis: its step, or its
+    /// timestamp in microseconds.
+    fn position(&self, point: &MetricPoint) -> f64 {
+        match self {
+            Self::Steps(..) => point.step.unwrap_or_default() as f64,
+            Self::Time(..) => point.timestamp.timestamp_micros() as f64,
+        }
+    }
+}
+
+impl Default for MetricRange {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+/// Aggregates over the points of a series within a [`MetricRange`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MetricSummary {
+    pub count: u64,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    /// Value of the last point along the range's axis.
+    pub last: f64,
+}
+
+/// Summarizes `points`, which must already be ordered along their range's
+/// axis. Returns `None` when there are no points.
+pub(crate) fn summarize<'a>(
+    points: impl IntoIterator<Item = &'a MetricPoint>,
+) -> Option<MetricSummary> {
+    let mut points = points.into_iter();
+    let first = points.next()?.value;
+    let mut summary = MetricSummary {
+        count: 1,
+        min: first,
+        max: first,
+        mean: 0.0,
+        last: first,
+    };
+    let mut sum = first;
+
+    for point in points {
+        summary.count += 1;
+        summary.min = summary.min.min(point.value);
+        summary.max = summary.max.max(point.value);
+        summary.last = point.value;
+        sum += point.value;
+    }
+    summary.mean = sum / summary.count as f64;
+
+    Some(summary)
+}
+
+/// How [`MetricStore::downsample_metric`](crate::MetricStore::downsample_metric)
+/// reduces a series to at most N points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Downsample {
+    /// Largest-Triangle-Three-Buckets: keeps the original points that best
+    /// preserve the shape of the series, including its first and last.
+    #[default]
+    Lttb,
+    /// Splits the range into N equal-width buckets along its axis. Each
+    /// non-empty bucket becomes one point holding the mean of its values,
+    /// at the bucket's earliest step and timestamp.
+    BucketMean,
+}
+
+/// Reduces `points`, ordered along `range`'s axis, to at most `max_points`.
+/// Series that already fit are returned unchanged.
+pub(crate) fn downsample(
+    range: &MetricRange,
+    points: Vec<MetricPoint>,
+    max_points: usize,
+    method: Downsample,
+) -> Vec<MetricPoint> {
+    if points.len() <= max_points {
+        return points;
+    }
+    match method {
+        Downsample::Lttb => lttb(range, points, max_points),
+        Downsample::BucketMean => bucket_means(range, points, max_points),
+    }
+}
+
+fn lttb(range: &MetricRange, points: Vec<MetricPoint>, threshold: usize) -> Vec<MetricPoint> {
+    let n = points.len();
+    match threshold {
+        0 => return Vec::new(),
+        1 => return points.into_iter().take(1).collect(),
+        2 => return vec![points[0].clone(), points[n - 1].clone()],
+        _ => {}
+    }
+
+    let x = |i: usize| range.position(&points[i]);
+    let y = |i: usize| points[i].value;
+
+    // The first and last points are always kept; the rest are split into
+    // `threshold - 2` buckets, and from each the point forming the largest
+    // triangle with the previously kept point and the next bucket's average
+    // is kept.
+    let every = (n - 2) as f64 / (threshold - 2) as f64;
+    let mut kept = Vec::with_capacity(threshold);
+    kept.push(0);
+    let mut previous = 0;
+
+    for bucket in 0..threshold - 2 {
+        let next_start = ((bucket + 1) as f64 * every) as usize + 1;
+        let next_end = (((bucket + 2) as f64 * every) as usize + 1).min(n);
+        let next_len = (next_end - next_start) as f64;
+        let average_x = (next_start..next_end).map(x).sum::<f64>() / next_len;
+        let average_y = (next_start..next_end).map(y).sum::<f64>() / next_len;
+
+        let start = (bucket as f64 * every) as usize + 1;
+        let end = next_start;
+        let (previous_x, previous_y) = (x(previous), y(previous));
+
+        let mut largest = f64::NEG_INFINITY;
+        let mut chosen = start;
+        for i in start..end {
+            let area = ((previous_x - average_x) * (y(i) - previous_y)
+                - (previous_x - x(i)) * (average_y - previous_y))
+                .abs();
+            if area > largest {
+                largest = area;
+                chosen = i;
+            }
+        }
+
+        kept.push(chosen);
+        previous = chosen;
+    }
+    kept.push(n - 1);
+
+    let mut points: Vec<Option<MetricPoint>> = points.into_iter().map(Some).collect();
+    kept.into_iter().filter_map(|i| points[i].take()).collect()
+}
+
+fn bucket_means(range: &MetricRange, points: Vec<MetricPoint>, buckets: usize) -> Vec<MetricPoint> {
+    if buckets == 0 {
+        return Vec::new();
+    }
+
+    let bucket_of: Box<dyn Fn(&MetricPoint) -> usize> = match range {
+        MetricRange::Steps(..) => {
+            let steps = || points.iter().filter_map(|point| point.step);
+            let first = steps().min().unwrap_or_default();
+            // Widened so a series spanning the whole u64 range can't overflow.
+            let width = (steps().max().unwrap_or_default() - first) as u128 + 1;
+            Box::new(move |point| {
+                let offset = point.step.unwrap_or_default() - first;
+                (offset as u128 * buckets as u128 / width) as usize
+            })
+        }
+        MetricRange::Time(..) => {
+            let first = points.iter().map(|point| point.timestamp).min();
+            let last = points.iter().map(|point| point.timestamp).max();
+            let first = first.map_or(0.0, |t| t.timestamp_micros() as f64);
+            let span = last.map_or(0.0, |t| t.timestamp_micros() as f64) - first;
+            let span = if span > 0.0 { span } else { 1.0 };
+            Box::new(move |point| {
+                let offset = point.timestamp.timestamp_micros() as f64 - first;
+                ((offset * buckets as f64 / span) as usize).min(buckets - 1)
+            })
+        }
+    };
+
+    let mut sums: Vec<Option<(MetricPoint, f64, u64)>> = vec![None; buckets];
+    for point in &points {
+        match &mut sums[bucket_of(point)] {
+            Some((earliest, sum, count)) => {
+                earliest.step = min_present(earliest.step, point.step);
+                earliest.epoch = min_present(earliest.epoch, point.epoch);
+                earliest.timestamp = earliest.timestamp.min(point.timestamp);
+                *sum += point.value;
+                *count += 1;
+            }
+            empty => *empty = Some((point.clone(), point.value, 1)),
+        }
+    }
+
+    sums.into_iter()
+        .flatten()
+        .map(|(point, sum, count)| MetricPoint {
+            value: sum / count as f64,
+            ..point
+        })
+        .collect()
+}
+
+/// The smaller of two optional values, ignoring missing ones like SQL's `MIN`.
+fn min_present(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
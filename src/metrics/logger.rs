@@ -1,123 +1,318 @@
 use crate::metrics::store::{MetricPoint, MetricStore};
-use crate::Result;
+use crate::{Result, TrackerError};
 use chrono::Utc;
-use std::sync::Arc;
-use tokio::sync::{broadcast, Mutex};
+use std::sync::{Arc, Mutex as StdMutex, MutexGuard, PoisonError};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot, Mutex};
+use tokio::task::JoinHandle;
+use tokio::time::{Interval, MissedTickBehavior};
 use uuid::Uuid;
 
 #[derive(Debug, Clone)]
 pub struct MetricLoggerConfig {
+    /// Number of buffered points that triggers a flush.
     pub buffer_size: usize,
-    pub flush_interval: std::time::Duration,
+    /// Longest time a point waits in the buffer before it is flushed.
+    /// `Duration::ZERO` turns the timer off, so points are only flushed when
+    /// the buffer fills or on `flush` and `shutdown`.
+    pub flush_interval: Duration,
+    /// Number of pending log calls queued for the worker. Once the queue is
+    /// full, logging waits for the worker, so a slow store slows loggers
+    /// down instead of growing memory without bound.
+    pub queue_capacity: usize,
 }
 
 impl Default for MetricLoggerConfig {
     fn default() -> Self {
         Self {
             buffer_size: 1000,
-            flush_interval: std::time::Duration::from_secs(60),
+            flush_interval: Duration::from_secs(60),
+            queue_capacity: 1024,
         }
     }
 }
 
+enum Command {
+    Log(MetricPoint),
+    LogBatch(Vec<MetricPoint>),
+    Flush(oneshot::Sender<Result<()>>),
+    Shutdown(oneshot::Sender<Result<()>>),
+}
+
+/// What the worker has yet to write: the points it buffered and the
+/// commands still queued for it. Shared with the handles so that the points
+/// can be taken over when the worker cannot run, e.g. while the runtime
+/// shuts down. The lock is never held across an `.await`.
+struct Backlog {
+    queue: mpsc::Receiver<Command>,
+    buffer: Vec<MetricPoint>,
+}
+
+fn lock(backlog: &StdMutex<Backlog>) -> MutexGuard<'_, Backlog> {
+    backlog.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Buffers metric points for a run and writes them to a `MetricStore` from a
+/// background worker.
+///
+/// The worker flushes whenever `buffer_size` points are buffered and at
+/// least every `flush_interval`. `MetricLogger` is a cheap handle to that
+/// worker: clones log to the same buffer, so it can be shared between tasks.
+/// [`MetricLogger::shutdown`] flushes what is left and stops the worker;
+/// dropping every handle does the same in the background.
+///
+/// Every logger has its own worker and stores need no global lock, so for
+/// the highest throughput give each producer its own logger rather than
+/// sharing one.
+///
+/// Must be created from within a tokio runtime.
+#[derive(Clone)]
 pub struct MetricLogger {
     run_id: Uuid,
-    store: Arc<Mutex<dyn MetricStore>>,
-    buffer: Vec<MetricPoint>,
-    config: MetricLoggerConfig,
-    shutdown: broadcast::Sender<()>,
+    epoch: Option<u64>,
+    commands: mpsc::Sender<Command>,
+    backlog: Arc<StdMutex<Backlog>>,
+    worker: Arc<Mutex<Option<JoinHandle<()>>>>,
 }
 
 impl MetricLogger {
-    pub fn new(
-        run_id: Uuid,
-        store: Arc<Mutex<dyn MetricStore>>,
-        config: MetricLoggerConfig,
-    ) -> Self {
-        let (shutdown, _) = broadcast::channel(1);
+    pub fn new(run_id: Uuid, store: Arc<dyn MetricStore>, config: MetricLoggerConfig) -> Self {
+        let (commands, queue) = mpsc::channel(config.queue_capacity.max(1));
+        let backlog = Arc::new(StdMutex::new(Backlog {
+            queue,
+            buffer: Vec::with_capacity(config.buffer_size),
+        }));
+        let worker = tokio::spawn(Worker::new(store, backlog.clone(), &config).run());
+
         Self {
             run_id,
-            store,
-            buffer: Vec::with_capacity(config.buffer_size),
-            config,
-            shutdown,
+            epoch: None,
+            commands,
+            backlog,
+            worker: Arc::new(Mutex::new(Some(worker))),
         }
     }
 
-    pub async fn log(&mut self, name: impl Into<String>, value: f64) -> Result<()> {
-        let point = MetricPoint {
-            run_id: self.run_id,
-            name: name.into(),
-            value,
-            timestamp: Utc::now(),
-        };
+    /// Sets the epoch recorded on every point this handle logs from now on.
+    pub fn set_epoch(&mut self, epoch: Option<u64>) {
+        self.epoch = epoch;
+    }
 
-        self.buffer.push(point);
+    pub async fn log(&self, name: impl Into<String>, value: f64) -> Result<()> {
+        let point = self.point(name.into(), value, None);
+        self.send(Command::Log(point)).await
+    }
 
-        if self.buffer.len() >= self.config.buffer_size {
-            self.flush().await?;
-        }
+    pub async fn log_at_step(&self, name: impl Into<String>, value: f64, step: u64) -> Result<()> {
+        let point = self.point(name.into(), value, Some(step));
+        self.send(Command::Log(point)).await
+    }
 
-        Ok(())
+    pub async fn log_batch(&self, metrics: Vec<(&str, f64)>) -> Result<()> {
+        self.log_points(metrics, None).await
     }
 
-    pub async fn log_batch(&mut self, metrics: Vec<(&str, f64)>) -> Result<()> {
+    pub async fn log_batch_at_step(&self, metrics: Vec<(&str, f64)>, step: u64) -> Result<()> {
+        self.log_points(metrics, Some(step)).await
+    }
+
+    async fn log_points(&self, metrics: Vec<(&str, f64)>, step: Option<u64>) -> Result<()> {
         let timestamp = Utc::now();
-        let points: Vec<_> = metrics
+        let points = metrics
             .into_iter()
             .map(|(name, value)| MetricPoint {
-                run_id: self.run_id,
-                name: name.to_string(),
-                value,
                 timestamp,
+                ..self.point(name.to_string(), value, step)
             })
             .collect();
 
-        self.buffer.extend(points);
+        self.send(Command::LogBatch(points)).await
+    }
 
-        if self.buffer.len() >= self.config.buffer_size {
-            self.flush().await?;
+    fn point(&self, name: String, value: f64, step: Option<u64>) -> MetricPoint {
+        MetricPoint {
+            run_id: self.run_id,
+            name,
+            value,
+            timestamp: Utc::now(),
+            step,
+            epoch: self.epoch,
         }
+    }
 
-        Ok(())
+    /// Writes every point logged so far to the store.
+    pub async fn flush(&self) -> Result<()> {
+        let (reply, flushed) = oneshot::channel();
+        self.send(Command::Flush(reply)).await?;
+        flushed.await.map_err(|_| stopped())?
     }
 
-    pub async fn flush(&mut self) -> Result<()> {
-        if self.buffer.is_empty() {
+    /// Flushes the remaining points and waits for the worker to exit. Later
+    /// calls on any handle of this logger return an error.
+    pub async fn shutdown(&self) -> Result<()> {
+        let Some(worker) = self.worker.lock().await.take() else {
             return Ok(());
+        };
+
+        let (reply, flushed) = oneshot::channel();
+        let result = match self.commands.send(Command::Shutdown(reply)).await {
+            Ok(()) => flushed.await.map_err(|_| stopped())?,
+            Err(_) => Err(stopped()),
+        };
+        worker
+            .await
+            .map_err(|e| TrackerError::InvalidOperation(e.to_string()))?;
+
+        result
+    }
+
+    /// Takes every point logged but not yet written, including points still
+    /// queued for the worker, so that they can be written without it.
+    /// `flush` and `shutdown` calls waiting in the queue fail as if the
+    /// logger had stopped.
+    pub(crate) fn take_unflushed(&self) -> Vec<MetricPoint> {
+        let mut backlog = lock(&self.backlog);
+        let Backlog { queue, buffer } = &mut *backlog;
+        while let Ok(command) = queue.try_recv() {
+            match command {
+                Command::Log(point) => buffer.push(point),
+                Command::LogBatch(points) => buffer.extend(points),
+                Command::Flush(_) | Command::Shutdown(_) => {}
+            }
+        }
+        std::mem::take(buffer)
+    }
+
+    async fn send(&self, command: Command) -> Result<()> {
+        self.commands.send(command).await.map_err(|_| stopped())
+    }
+}
+
+/// Waits for the next timer flush, or forever when the timer is off.
+async fn tick(interval: &mut Option<Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
         }
+        None => std::future::pending().await,
+    }
+}
 
-        let points = std::mem::take(&mut self.buffer);
-        let store = self.store.lock().await;
-        store.store_metrics(&points).await?;
+fn stopped() -> TrackerError {
+    TrackerError::InvalidOperation("MetricLogger has been shut down".to_string())
+}
 
-        Ok(())
+struct Worker {
+    store: Arc<dyn MetricStore>,
+    backlog: Arc<StdMutex<Backlog>>,
+    buffer_size: usize,
+    flush_interval: Option<Duration>,
+}
+
+impl Worker {
+    fn new(
+        store: Arc<dyn MetricStore>,
+        backlog: Arc<StdMutex<Backlog>>,
+        config: &MetricLoggerConfig,
+    ) -> Self {
+        Self {
+            store,
+            backlog,
+            buffer_size: config.buffer_size.max(1),
+            // `tokio::time::interval` panics on a zero period.
+            flush_interval: Some(config.flush_interval).filter(|interval| !interval.is_zero()),
+        }
     }
 
-    pub async fn start_auto_flush(&mut self) -> Result<()> {
-        let mut interval = tokio::time::interval(self.config.flush_interval);
-        let mut shutdown = self.shutdown.subscribe();
+    async fn run(self) {
+        let mut interval = self.flush_interval.map(tokio::time::interval);
+        if let Some(interval) = &mut interval {
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            interval.tick().await;
+        }
 
         loop {
             tokio::select! {
-                _ = interval.tick() => {
-                    if !self.buffer.is_empty() {
-                        self.flush().await?;
+                command = self.recv() => match command {
+                    Some(Command::Log(point)) => {
+                        self.buffer(|buffer| buffer.push(point));
+                        self.flush_if_full().await;
+                    }
+                    Some(Command::LogBatch(points)) => {
+                        self.buffer(|buffer| buffer.extend(points));
+                        self.flush_if_full().await;
+                    }
+                    Some(Command::Flush(reply)) => {
+                        let _ = reply.send(self.flush().await);
                     }
-                }
-                _ = shutdown.recv() => {
-                    if !self.buffer.is_empty() {
-                        self.flush().await?;
+                    Some(Command::Shutdown(reply)) => {
+                        lock(&self.backlog).queue.close();
+                        // Commands that raced with the shutdown are still handled.
+                        while let Some(command) = self.recv().await {
+                            self.absorb(command).await;
+                        }
+                        let _ = reply.send(self.flush().await);
+                        return;
                     }
-                    break;
-                }
+                    None => {
+                        self.flush_in_background().await;
+                        return;
+                    }
+                },
+                _ = tick(&mut interval) => self.flush_in_background().await,
             }
         }
-        Ok(())
     }
 
-    pub async fn shutdown(&self) -> Result<()> {
-        let _ = self.shutdown.send(());
+    async fn recv(&self) -> Option<Command> {
+        std::future::poll_fn(|cx| lock(&self.backlog).queue.poll_recv(cx)).await
+    }
+
+    fn buffer<T>(&self, f: impl FnOnce(&mut Vec<MetricPoint>) -> T) -> T {
+        f(&mut lock(&self.backlog).buffer)
+    }
+
+    /// Handles a command that arrived after shutdown began.
+    async fn absorb(&self, command: Command) {
+        match command {
+            Command::Log(point) => self.buffer(|buffer| buffer.push(point)),
+            Command::LogBatch(points) => self.buffer(|buffer| buffer.extend(points)),
+            Command::Flush(reply) | Command::Shutdown(reply) => {
+                let _ = reply.send(self.flush().await);
+            }
+        }
+    }
+
+    async fn flush_if_full(&self) {
+        if self.buffer(|buffer| buffer.len()) >= self.buffer_size {
+            self.flush_in_background().await;
+        }
+    }
+
+    /// Flushes on the worker's own schedule. A failed write keeps its points
+    /// buffered, so they are retried by the next flush and the error reaches
+    /// whoever calls `flush` or `shutdown`.
+    async fn flush_in_background(&self) {
+        if let Err(e) = self.flush().await {
+            let pending = self.buffer(|buffer| buffer.len());
+            tracing::warn!(error = %e, pending, "failed to flush metrics");
+        }
+    }
+
+    async fn flush(&self) -> Result<()> {
+        let points = self.buffer(std::mem::take);
+        if points.is_empty() {
+            return Ok(());
+        }
+
+        if let Err(e) = self.store.store_metrics(&points).await {
+            // Put the points back ahead of any logged since.
+            self.buffer(|buffer| {
+                let newer = std::mem::replace(buffer, points);
+                buffer.extend(newer);
+            });
+            return Err(e);
+        }
         Ok(())
     }
 }
@@ -1,7 +1,11 @@
+pub(crate) mod aggregate;
 pub(crate) mod logger;
+pub(crate) mod sqlite;
 pub mod store;
 pub mod system;
 
+pub use aggregate::{Downsample, MetricRange, MetricSummary};
 pub use logger::{MetricLogger, MetricLoggerConfig};
+pub use sqlite::SqliteMetricStore;
 pub use store::{InMemoryMetricStore, MetricPoint, MetricStore};
 pub use system::SystemMetrics;
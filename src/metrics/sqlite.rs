@@ -0,0 +1,516 @@
+use crate::metrics::aggregate::{self, Downsample, MetricRange, MetricSummary};
+use crate::metrics::store::{MetricPoint, MetricStore};
+use crate::storage::database::ID_CHUNK_SIZE;
+use crate::storage::Database;
+use crate::{Result, TrackerError};
+use async_trait::async_trait;
+use sqlx::{
+    sqlite::{SqlitePool, SqliteRow},
+    Encode, QueryBuilder, Row, Sqlite, Transaction, Type,
+};
+use std::collections::HashMap;
+use std::ops::Bound;
+use uuid::Uuid;
+
+// Six bound parameters per row keeps each INSERT well under SQLite's
+// host parameter limit.
+const INSERT_CHUNK_SIZE: usize = 1000;
+
+/// A `MetricStore` that persists points to the `metrics` table of the
+/// tracker's SQLite database.
+pub struct SqliteMetricStore {
+    pool: SqlitePool,
+}
+
+impl SqliteMetricStore {
+    pub fn new(database: &Database) -> Self {
+        Self {
+            pool: database.pool().clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl MetricStore for SqliteMetricStore {
+    fn new() -> Result<Self> {
+        Err(TrackerError::InvalidOperation(
+            "SqliteMetricStore requires a database; use SqliteMetricStore::new(&database)"
+                .to_string(),
+        ))
+    }
+
+    async fn store_metrics(&self, metrics: &[MetricPoint]) -> Result<()> {
+        if metrics.is_empty() {
+            return Ok(());
+        }
+
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+        insert_metrics(&mut tx, metrics).await?;
+        tx.commit()
+            .await
+            .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    async fn get_metrics(&self, run_id: Uuid, metric_name: &str) -> Result<Vec<MetricPoint>> {
+        let rows = sqlx::query(
+            "SELECT run_id, name, value, timestamp, step, epoch FROM metrics
+            WHERE run_id = ? AND name = ?
+            ORDER BY timestamp, id",
+        )
+        .bind(run_id)
+        .bind(metric_name)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+        rows.iter().map(point_from_row).collect()
+    }
+
+    async fn get_latest_metric(
+        &self,
+        run_id: Uuid,
+        metric_name: &str,
+    ) -> Result<Option<MetricPoint>> {
+        let row = sqlx::query(
+            "SELECT run_id, name, value, timestamp, step, epoch FROM metrics
+            WHERE run_id = ? AND name = ?
+            ORDER BY timestamp DESC, id DESC
+            LIMIT 1",
+        )
+        .bind(run_id)
+        .bind(metric_name)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+        row.as_ref().map(point_from_row).transpose()
+    }
+
+    async fn list_metric_keys(&self, run_id: Uuid) -> Result<Vec<String>> {
+        let rows = sqlx::query("SELECT DISTINCT name FROM metrics WHERE run_id = ? ORDER BY name")
+            .bind(run_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+        rows.iter()
+            .map(|row| {
+                row.try_get("name")
+                    .map_err(|e| TrackerError::Database(e.to_string()))
+            })
+            .collect()
+    }
+
+    async fn get_metrics_many(
+        &self,
+        run_ids: &[Uuid],
+        names: &[&str],
+    ) -> Result<HashMap<Uuid, HashMap<String, Vec<MetricPoint>>>> {
+        let mut runs: HashMap<Uuid, HashMap<String, Vec<MetricPoint>>> = run_ids
+            .iter()
+            .map(|&run_id| (run_id, HashMap::new()))
+            .collect();
+        if run_ids.is_empty() {
+            return Ok(runs);
+        }
+
+        // Chunks keep the bound ids and names under SQLite's parameter limit.
+        // Each run's points come from a single query per name chunk, so every
+        // series keeps its order.
+        let name_chunks: Vec<&[&str]> = if names.is_empty() {
+            vec![&[]]
+        } else {
+            names.chunks(ID_CHUNK_SIZE).collect()
+        };
+        for ids in run_ids.chunks(ID_CHUNK_SIZE) {
+            for names in &name_chunks {
+                let mut query: QueryBuilder<Sqlite> = QueryBuilder::new(
+                    "SELECT run_id, name, value, timestamp, step, epoch FROM metrics \
+                    WHERE run_id IN (",
+                );
+                let mut bound = query.separated(", ");
+                for run_id in ids {
+                    bound.push_bind(*run_id);
+                }
+                query.push(")");
+                if !names.is_empty() {
+                    query.push(" AND name IN (");
+                    let mut bound = query.separated(", ");
+                    for name in names.iter() {
+                        bound.push_bind(name.to_string());
+                    }
+                    query.push(")");
+                }
+                query.push(" ORDER BY timestamp, id");
+
+                let rows = query
+                    .build()
+                    .fetch_all(&self.pool)
+                    .await
+                    .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+                for row in rows {
+                    let point = point_from_row(&row)?;
+                    if let Some(series) = runs.get_mut(&point.run_id) {
+                        series.entry(point.name.clone()).or_default().push(point);
+                    }
+                }
+            }
+        }
+
+        Ok(runs)
+    }
+
+    async fn get_metrics_by_step(
+        &self,
+        run_id: Uuid,
+        metric_name: &str,
+    ) -> Result<Vec<MetricPoint>> {
+        let rows = sqlx::query(
+            "SELECT run_id, name, value, timestamp, step, epoch FROM metrics
+            WHERE run_id = ? AND name = ? AND step IS NOT NULL
+            ORDER BY step, id",
+        )
+        .bind(run_id)
+        .bind(metric_name)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+        rows.iter().map(point_from_row).collect()
+    }
+
+    async fn get_metrics_in_range(
+        &self,
+        run_id: Uuid,
+        metric_name: &str,
+        range: MetricRange,
+    ) -> Result<Vec<MetricPoint>> {
+        let mut query: QueryBuilder<Sqlite> =
+            QueryBuilder::new("SELECT run_id, name, value, timestamp, step, epoch");
+        push_series(&mut query, run_id, metric_name, &range);
+        query.push(" ORDER BY ").push(axis(&range)).push(", id");
+
+        let rows = query
+            .build()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+        rows.iter().map(point_from_row).collect()
+    }
+
+    async fn summarize_metric(
+        &self,
+        run_id: Uuid,
+        metric_name: &str,
+        range: MetricRange,
+    ) -> Result<Option<MetricSummary>> {
+        let mut query: QueryBuilder<Sqlite> = QueryBuilder::new(
+            "SELECT COUNT(*) AS count, MIN(value) AS min, MAX(value) AS max, \
+            AVG(value) AS mean, (SELECT value",
+        );
+        push_series(&mut query, run_id, metric_name, &range);
+        query
+            .push(" ORDER BY ")
+            .push(axis(&range))
+            .push(" DESC, id DESC LIMIT 1) AS last");
+        push_series(&mut query, run_id, metric_name, &range);
+
+        let row = query
+            .build()
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+        let count: i64 = row
+            .try_get("count")
+            .map_err(|e| TrackerError::Database(e.to_string()))?;
+        if count == 0 {
+            return Ok(None);
+        }
+        let get = |column: &str| -> Result<f64> {
+            row.try_get(column)
+                .map_err(|e| TrackerError::Database(e.to_string()))
+        };
+
+        Ok(Some(MetricSummary {
+            count: count as u64,
+            min: get("min")?,
+            max: get("max")?,
+            mean: get("mean")?,
+            last: get("last")?,
+        }))
+    }
+
+    async fn downsample_metric(
+        &self,
+        run_id: Uuid,
+        metric_name: &str,
+        range: MetricRange,
+        max_points: usize,
+        method: Downsample,
+    ) -> Result<Vec<MetricPoint>> {
+        if method == Downsample::Lttb {
+            // LTTB needs every point's neighbours, so it runs on the fetched
+            // series.
+            let points = self
+                .get_metrics_in_range(run_id, metric_name, range)
+                .await?;
+            return Ok(aggregate::downsample(&range, points, max_points, method));
+        }
+        if max_points == 0 {
+            return Ok(Vec::new());
+        }
+
+        let position = match range {
+            MetricRange::Steps(..) => "step",
+            MetricRange::Time(..) => "julianday(timestamp)",
+        };
+        let mut query: QueryBuilder<Sqlite> = QueryBuilder::new("SELECT COUNT(*) AS count, MIN(");
+        query
+            .push(position)
+            .push(") AS first, MAX(")
+            .push(position)
+            .push(") AS last");
+        push_series(&mut query, run_id, metric_name, &range);
+        let row = query
+            .build()
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+        let count: i64 = row
+            .try_get("count")
+            .map_err(|e| TrackerError::Database(e.to_string()))?;
+        if count as usize <= max_points {
+            return self.get_metrics_in_range(run_id, metric_name, range).await;
+        }
+
+        // Buckets are numbered as in the in-memory store. Time offsets are
+        // measured in SQLite's millisecond-precision Julian days rather than
+        // microseconds, so a point right on a bucket edge may land in the
+        // neighbouring bucket.
+        let mut query: QueryBuilder<Sqlite> = QueryBuilder::new(
+            "SELECT run_id, name, AVG(value) AS value, MIN(timestamp) AS timestamp, \
+            MIN(step) AS step, MIN(epoch) AS epoch, ",
+        );
+        match range {
+            MetricRange::Steps(..) => {
+                let first: i64 = row
+                    .try_get("first")
+                    .map_err(|e| TrackerError::Database(e.to_string()))?;
+                let last: i64 = row
+                    .try_get("last")
+                    .map_err(|e| TrackerError::Database(e.to_string()))?;
+                // Computed in REAL, as the integer product can overflow i64
+                // for large steps; rounding is clamped to the last bucket.
+                let width = (last as i128 - first as i128 + 1) as f64;
+                query
+                    .push("MIN(CAST((CAST(step AS REAL) - ")
+                    .push_bind(first as f64)
+                    .push(") * ")
+                    .push_bind(max_points as f64)
+                    .push(" / ")
+                    .push_bind(width)
+                    .push(" AS INTEGER), ")
+                    .push_bind(max_points as i64 - 1)
+                    .push(")");
+            }
+            MetricRange::Time(..) => {
+                let first: f64 = row
+                    .try_get("first")
+                    .map_err(|e| TrackerError::Database(e.to_string()))?;
+                let last: f64 = row
+                    .try_get("last")
+                    .map_err(|e| TrackerError::Database(e.to_string()))?;
+                let span = if last > first { last - first } else { 1.0 };
+                query
+                    .push("MIN(CAST((julianday(timestamp) - ")
+                    .push_bind(first)
+                    .push(") * ")
+                    .push_bind(max_points as f64)
+                    .push(" / ")
+                    .push_bind(span)
+                    .push(" AS INTEGER), ")
+                    .push_bind(max_points as i64 - 1)
+                    .push(")");
+            }
+        }
+        query.push(" AS bucket");
+        push_series(&mut query, run_id, metric_name, &range);
+        query.push(" GROUP BY bucket ORDER BY bucket");
+
+        let rows = query
+            .build()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+        rows.iter().map(point_from_row).collect()
+    }
+}
+
+/// Column a range's points are ordered by.
+fn axis(range: &MetricRange) -> &'static str {
+    match range {
+        MetricRange::Steps(..) => "step",
+        MetricRange::Time(..) => "timestamp",
+    }
+}
+
+/// Pushes the `FROM ... WHERE` clause selecting a series' points within
+/// `range`.
+fn push_series(
+    query: &mut QueryBuilder<'_, Sqlite>,
+    run_id: Uuid,
+    metric_name: &str,
+    range: &MetricRange,
+) {
+    query
+        .push(" FROM metrics WHERE run_id = ")
+        .push_bind(run_id)
+        .push(" AND name = ")
+        .push_bind(metric_name.to_string());
+
+    match *range {
+        MetricRange::Steps(start, end) => {
+            query.push(" AND step IS NOT NULL");
+            // Stored steps never exceed `i64::MAX`, so a larger start bound
+            // excludes every point and a larger end bound excludes none.
+            let start = match start {
+                Bound::Included(step) | Bound::Excluded(step) if step > i64::MAX as u64 => {
+                    query.push(" AND 0");
+                    Bound::Unbounded
+                }
+                start => start.map(|step| step as i64),
+            };
+            let end = match end {
+                Bound::Included(step) | Bound::Excluded(step) if step > i64::MAX as u64 => {
+                    Bound::Unbounded
+                }
+                end => end.map(|step| step as i64),
+            };
+            push_bounds(query, "step", start, end);
+        }
+        MetricRange::Time(start, end) => push_bounds(query, "timestamp", start, end),
+    }
+}
+
+fn push_bounds<'args, T>(
+    query: &mut QueryBuilder<'args, Sqlite>,
+    column: &str,
+    start: Bound<T>,
+    end: Bound<T>,
+) where
+    T: 'args + Encode<'args, Sqlite> + Type<Sqlite> + Send,
+{
+    match start {
+        Bound::Included(value) => {
+            query.push(format!(" AND {} >= ", column)).push_bind(value);
+        }
+        Bound::Excluded(value) => {
+            query.push(format!(" AND {} > ", column)).push_bind(value);
+        }
+        Bound::Unbounded => {}
+    }
+    match end {
+        Bound::Included(value) => {
+            query.push(format!(" AND {} <= ", column)).push_bind(value);
+        }
+        Bound::Excluded(value) => {
+            query.push(format!(" AND {} < ", column)).push_bind(value);
+        }
+        Bound::Unbounded => {}
+    }
+}
+
+/// Inserts `metrics` as part of `tx`, so that they are written together
+/// with whatever else the transaction saves.
+pub(crate) async fn insert_metrics(
+    tx: &mut Transaction<'_, Sqlite>,
+    metrics: &[MetricPoint],
+) -> Result<()> {
+    let rows = metrics
+        .iter()
+        .map(|point| {
+            Ok((
+                point,
+                stored_integer(point, "step", point.step)?,
+                stored_integer(point, "epoch", point.epoch)?,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    for chunk in rows.chunks(INSERT_CHUNK_SIZE) {
+        let mut query: QueryBuilder<Sqlite> =
+            QueryBuilder::new("INSERT INTO metrics (run_id, name, step, epoch, value, timestamp) ");
+        query.push_values(chunk, |mut row, (point, step, epoch)| {
+            row.push_bind(point.run_id)
+                .push_bind(&point.name)
+                .push_bind(*step)
+                .push_bind(*epoch)
+                .push_bind(point.value)
+                .push_bind(point.timestamp);
+        });
+        query
+            .build()
+            .execute(&mut **tx)
+            .await
+            .map_err(|e| TrackerError::Database(e.to_string()))?;
+    }
+
+    Ok(())
+}
+
+/// Converts a step or epoch to SQLite's signed integer. Values above
+/// `i64::MAX` are rejected rather than wrapped to negative numbers, which
+/// would sort and filter them before every other point.
+fn stored_integer(point: &MetricPoint, field: &str, value: Option<u64>) -> Result<Option<i64>> {
+    value
+        .map(|value| {
+            i64::try_from(value).map_err(|_| {
+                TrackerError::InvalidOperation(format!(
+                    "{} {} of metric '{}' is above {}, the largest the SQLite store can hold",
+                    field,
+                    value,
+                    point.name,
+                    i64::MAX
+                ))
+            })
+        })
+        .transpose()
+}
+
+fn point_from_row(row: &SqliteRow) -> Result<MetricPoint> {
+    let step: Option<i64> = row
+        .try_get("step")
+        .map_err(|e| TrackerError::Database(e.to_string()))?;
+    let epoch: Option<i64> = row
+        .try_get("epoch")
+        .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+    Ok(MetricPoint {
+        run_id: row
+            .try_get("run_id")
+            .map_err(|e| TrackerError::Database(e.to_string()))?,
+        name: row
+            .try_get("name")
+            .map_err(|e| TrackerError::Database(e.to_string()))?,
+        value: row
+            .try_get("value")
+            .map_err(|e| TrackerError::Database(e.to_string()))?,
+        timestamp: row
+            .try_get("timestamp")
+            .map_err(|e| TrackerError::Database(e.to_string()))?,
+        step: step.map(|step| step as u64),
+        epoch: epoch.map(|epoch| epoch as u64),
+    })
+}
@@ -1,8 +1,12 @@
+use crate::metrics::aggregate::{self, Downsample, MetricRange, MetricSummary};
 use crate::Result;
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::sync::Mutex;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::sync::{RwLock, RwLockWriteGuard};
 use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -11,6 +15,13 @@ pub struct MetricPoint {
     pub name: String,
     pub value: f64,
     pub timestamp: DateTime<Utc>,
+    /// Training step (iteration) the value was recorded at, used to align
+    /// series from runs that progressed at different speeds. The SQLite
+    /// store holds steps and epochs up to `i64::MAX` and rejects larger ones.
+    #[serde(default)]
+    pub step: Option<u64>,
+    #[serde(default)]
+    pub epoch: Option<u64>,
 }
 
 #[async_trait]
@@ -25,18 +36,130 @@ pub trait MetricStore: Send + Sync {
         run_id: Uuid,
         metric_name: &str,
     ) -> Result<Option<MetricPoint>>;
+
+    /// Returns the names of every metric logged for a run, sorted.
+    async fn list_metric_keys(&self, run_id: Uuid) -> Result<Vec<String>>;
+
+    /// Returns the named series of several runs at once, keyed by run and
+    /// then metric name, each ordered as by `get_metrics`. An empty `names`
+    /// fetches every metric of the runs. Every requested run has an entry,
+    /// but series without points are left out.
+    async fn get_metrics_many(
+        &self,
+        run_ids: &[Uuid],
+        names: &[&str],
+    ) -> Result<HashMap<Uuid, HashMap<String, Vec<MetricPoint>>>> {
+        let mut runs = HashMap::new();
+        for &run_id in run_ids {
+            let keys = if names.is_empty() {
+                self.list_metric_keys(run_id).await?
+            } else {
+                names.iter().map(|name| name.to_string()).collect()
+            };
+
+            let mut series = HashMap::new();
+            for name in keys {
+                let points = self.get_metrics(run_id, &name).await?;
+                if !points.is_empty() {
+                    series.insert(name, points);
+                }
+            }
+            runs.insert(run_id, series);
+        }
+        Ok(runs)
+    }
+
+    /// Returns the points of a series that carry a step, ordered by step.
+    async fn get_metrics_by_step(
+        &self,
+        run_id: Uuid,
+        metric_name: &str,
+    ) -> Result<Vec<MetricPoint>> {
+        let mut points: Vec<_> = self
+            .get_metrics(run_id, metric_name)
+            .await?
+            .into_iter()
+            .filter(|point| point.step.is_some())
+            .collect();
+        points.sort_by_key(|point| point.step);
+        Ok(points)
+    }
+
+    /// Returns the points of a series within `range`, ordered along its axis.
+    async fn get_metrics_in_range(
+        &self,
+        run_id: Uuid,
+        metric_name: &str,
+        range: MetricRange,
+    ) -> Result<Vec<MetricPoint>> {
+        let mut points: Vec<_> = self
+            .get_metrics(run_id, metric_name)
+            .await?
+            .into_iter()
+            .filter(|point| range.contains(point))
+            .collect();
+        range.sort(&mut points);
+        Ok(points)
+    }
+
+    /// Returns the count, min, max, mean and last value of a series within
+    /// `range`, or `None` if it has no points there.
+    async fn summarize_metric(
+        &self,
+        run_id: Uuid,
+        metric_name: &str,
+        range: MetricRange,
+    ) -> Result<Option<MetricSummary>> {
+        let points = self
+            .get_metrics_in_range(run_id, metric_name, range)
+            .await?;
+        Ok(aggregate::summarize(&points))
+    }
+
+    /// Returns the points of a series within `range` reduced to at most
+    /// `max_points`, e.g. to draw a chart of a long series.
+    async fn downsample_metric(
+        &self,
+        run_id: Uuid,
+        metric_name: &str,
+        range: MetricRange,
+        max_points: usize,
+        method: Downsample,
+    ) -> Result<Vec<MetricPoint>> {
+        let points = self
+            .get_metrics_in_range(run_id, metric_name, range)
+            .await?;
+        Ok(aggregate::downsample(&range, points, max_points, method))
+    }
 }
 
+// Enough shards that concurrent writers to different series rarely contend.
+const SHARD_COUNT: usize = 64;
+
+type Series = HashMap<Uuid, HashMap<String, Vec<MetricPoint>>>;
+
+/// Keeps metrics in memory, sharded by series so that concurrent writers to
+/// different series do not contend for the same lock.
 pub struct InMemoryMetricStore {
-    metrics: Mutex<Vec<MetricPoint>>,
+    shards: Box<[RwLock<Series>]>,
+    hasher: RandomState,
 }
 
 impl InMemoryMetricStore {
     pub fn new() -> Result<Self> {
         Ok(Self {
-            metrics: Mutex::new(Vec::new()),
+            shards: (0..SHARD_COUNT).map(|_| RwLock::default()).collect(),
+            hasher: RandomState::new(),
         })
     }
+
+    fn shard_index(&self, run_id: Uuid, metric_name: &str) -> usize {
+        self.hasher.hash_one((run_id, metric_name)) as usize % self.shards.len()
+    }
+
+    fn shard(&self, run_id: Uuid, metric_name: &str) -> &RwLock<Series> {
+        &self.shards[self.shard_index(run_id, metric_name)]
+    }
 }
 
 #[async_trait]
@@ -46,18 +169,40 @@ impl MetricStore for InMemoryMetricStore {
     }
 
     async fn store_metrics(&self, metrics: &[MetricPoint]) -> Result<()> {
-        let mut store = self.metrics.lock().unwrap();
-        store.extend_from_slice(metrics);
+        // Batches usually hold runs of the same series, so keep the current
+        // shard locked until a point belongs elsewhere.
+        let mut locked: Option<(usize, RwLockWriteGuard<'_, Series>)> = None;
+
+        for point in metrics {
+            let index = self.shard_index(point.run_id, &point.name);
+            let guard = match &mut locked {
+                Some((current, guard)) if *current == index => guard,
+                _ => {
+                    locked = None;
+                    &mut locked
+                        .insert((index, self.shards[index].write().unwrap()))
+                        .1
+                }
+            };
+
+            guard
+                .entry(point.run_id)
+                .or_default()
+                .entry(point.name.clone())
+                .or_default()
+                .push(point.clone());
+        }
+
         Ok(())
     }
 
     async fn get_metrics(&self, run_id: Uuid, metric_name: &str) -> Result<Vec<MetricPoint>> {
-        let store = self.metrics.lock().unwrap();
-        Ok(store
-            .iter()
-            .filter(|m| m.run_id == run_id && m.name == metric_name)
+        let shard = self.shard(run_id, metric_name).read().unwrap();
+        Ok(shard
+            .get(&run_id)
+            .and_then(|series| series.get(metric_name))
             .cloned()
-            .collect())
+            .unwrap_or_default())
     }
 
     async fn get_latest_metric(
@@ -65,11 +210,97 @@ impl MetricStore for InMemoryMetricStore {
         run_id: Uuid,
         metric_name: &str,
     ) -> Result<Option<MetricPoint>> {
-        let store = self.metrics.lock().unwrap();
-        Ok(store
-            .iter()
-            .filter(|m| m.run_id == run_id && m.name == metric_name)
-            .max_by_key(|m| m.timestamp)
+        let shard = self.shard(run_id, metric_name).read().unwrap();
+        Ok(shard
+            .get(&run_id)
+            .and_then(|series| series.get(metric_name))
+            .and_then(|points| points.iter().max_by_key(|m| m.timestamp))
             .cloned())
     }
+
+    async fn list_metric_keys(&self, run_id: Uuid) -> Result<Vec<String>> {
+        let mut keys: Vec<String> = self
+            .shards
+            .iter()
+            .flat_map(|shard| {
+                let shard = shard.read().unwrap();
+                shard
+                    .get(&run_id)
+                    .map(|series| series.keys().cloned().collect::<Vec<_>>())
+                    .unwrap_or_default()
+            })
+            .collect();
+        keys.sort();
+        Ok(keys)
+    }
+
+    async fn get_metrics_many(
+        &self,
+        run_ids: &[Uuid],
+        names: &[&str],
+    ) -> Result<HashMap<Uuid, HashMap<String, Vec<MetricPoint>>>> {
+        let mut runs: HashMap<Uuid, HashMap<String, Vec<MetricPoint>>> = run_ids
+            .iter()
+            .map(|&run_id| (run_id, HashMap::new()))
+            .collect();
+
+        // A run's series are spread over the shards, so visit each shard
+        // once rather than once per series.
+        for shard in self.shards.iter() {
+            let shard = shard.read().unwrap();
+            for (run_id, found) in runs.iter_mut() {
+                let Some(series) = shard.get(run_id) else {
+                    continue;
+                };
+                let wanted = series
+                    .iter()
+                    .filter(|(name, points)| {
+                        !points.is_empty() && (names.is_empty() || names.contains(&name.as_str()))
+                    })
+                    .map(|(name, points)| (name.clone(), points.clone()));
+                found.extend(wanted);
+            }
+        }
+
+        Ok(runs)
+    }
+
+    async fn get_metrics_in_range(
+        &self,
+        run_id: Uuid,
+        metric_name: &str,
+        range: MetricRange,
+    ) -> Result<Vec<MetricPoint>> {
+        let mut points: Vec<_> = {
+            let shard = self.shard(run_id, metric_name).read().unwrap();
+            shard
+                .get(&run_id)
+                .and_then(|series| series.get(metric_name))
+                .into_iter()
+                .flatten()
+                .filter(|point| range.contains(point))
+                .cloned()
+                .collect()
+        };
+        range.sort(&mut points);
+        Ok(points)
+    }
+
+    async fn summarize_metric(
+        &self,
+        run_id: Uuid,
+        metric_name: &str,
+        range: MetricRange,
+    ) -> Result<Option<MetricSummary>> {
+        let shard = self.shard(run_id, metric_name).read().unwrap();
+        let mut points: Vec<&MetricPoint> = shard
+            .get(&run_id)
+            .and_then(|series| series.get(metric_name))
+            .into_iter()
+            .flatten()
+            .filter(|point| range.contains(point))
+            .collect();
+        range.sort(&mut points);
+        Ok(aggregate::summarize(points))
+    }
 }
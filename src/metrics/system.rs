@@ -4,10 +4,10 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use systemstat::{Platform, System};
-use tokio::sync::{broadcast, Mutex};
+use tokio::sync::broadcast;
 
 pub struct SystemMetrics {
-    logger: Arc<Mutex<MetricLogger>>,
+    logger: MetricLogger,
     interval: Duration,
     shutdown: broadcast::Sender<()>,
     is_running: Arc<AtomicBool>,
@@ -17,7 +17,7 @@ impl SystemMetrics {
     pub fn new(logger: MetricLogger, interval: Duration) -> Self {
         let (shutdown, _) = broadcast::channel(1);
         Self {
-            logger: Arc::new(Mutex::new(logger)),
+            logger,
             interval,
             shutdown,
             is_running: Arc::new(AtomicBool::new(false)),
@@ -55,8 +55,7 @@ impl SystemMetrics {
                             }
 
                             if !metrics.is_empty() {
-                                let mut logger_lock = logger.lock().await;
-                                logger_lock.log_batch(metrics).await?;
+                                logger.log_batch(metrics).await?;
                             }
                         }
                         _ = shutdown.recv() => {
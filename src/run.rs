@@ -1,54 +1,219 @@
+use crate::metrics::MetricPoint;
+use crate::{Result, TrackerError};
 use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::collections::HashMap;
 use uuid::Uuid;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum RunStatus {
+    Scheduled,
     Running,
+    Paused,
     Completed,
     Failed,
     Interrupted,
+    Killed,
+}
+
+impl RunStatus {
+    /// Terminal states have no outgoing transitions.
+    pub fn is_terminal(self) -> bool {
+        matches!(
+            self,
+            RunStatus::Completed | RunStatus::Failed | RunStatus::Interrupted | RunStatus::Killed
+        )
+    }
+
+    /// Whether the run currently occupies its experiment's active slot.
+    pub fn is_active(self) -> bool {
+        matches!(self, RunStatus::Running | RunStatus::Paused)
+    }
+
+    pub fn can_transition_to(self, next: RunStatus) -> bool {
+        use RunStatus::*;
+
+        matches!(
+            (self, next),
+            (Scheduled, Running | Killed)
+                | (Running, Paused | Completed | Failed | Interrupted | Killed)
+                | (Paused, Running | Failed | Interrupted | Killed)
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunTransition {
+    pub status: RunStatus,
+    pub at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Run {
     pub id: Uuid,
     pub experiment_id: Uuid,
+    /// The run this run was started under, if any.
+    #[serde(default)]
+    pub parent_run_id: Option<Uuid>,
     pub status: RunStatus,
-    pub metrics: HashMap<String, Vec<(DateTime<Utc>, f64)>>,
-    pub params: HashMap<String, String>,
+    /// Points logged on this handle. Runs read from the database leave this
+    /// empty unless loaded with `Database::load_run_metrics`.
+    pub metrics: HashMap<String, Vec<MetricPoint>>,
+    /// Parameters keyed by dotted path, e.g. `optimizer.lr`. Values keep
+    /// their JSON type, so numbers compare as numbers in run filters.
+    pub params: HashMap<String, Value>,
     pub tags: HashMap<String, String>,
     pub artifacts: Vec<String>,
     pub start_time: DateTime<Utc>,
     pub end_time: Option<DateTime<Utc>>,
+    /// Every status the run has entered, oldest first. Empty for runs
+    /// serialized before transitions were recorded.
+    #[serde(default)]
+    pub transitions: Vec<RunTransition>,
+    /// How many leading points of each series in `metrics` are already in
+    /// the database, so saving the run again only appends the rest.
+    #[serde(skip)]
+    pub(crate) saved_metrics: HashMap<String, usize>,
 }
 
 impl Run {
     pub fn new(experiment_id: Uuid) -> Self {
+        Self::with_status(experiment_id, RunStatus::Running)
+    }
+
+    /// Creates a run that is queued but has not started executing yet.
+    pub fn scheduled(experiment_id: Uuid) -> Self {
+        Self::with_status(experiment_id, RunStatus::Scheduled)
+    }
+
+    fn with_status(experiment_id: Uuid, status: RunStatus) -> Self {
+        let now = Utc::now();
         Self {
             id: Uuid::new_v4(),
             experiment_id,
-            status: RunStatus::Running,
+            parent_run_id: None,
+            status,
             metrics: HashMap::new(),
             params: HashMap::new(),
             tags: HashMap::new(),
             artifacts: Vec::new(),
-            start_time: Utc::now(),
+            start_time: now,
             end_time: None,
+            transitions: vec![RunTransition { status, at: now }],
+            saved_metrics: HashMap::new(),
         }
     }
 
+    /// Creates a run nested under this one, e.g. a sweep trial or a
+    /// cross-validation fold.
+    pub fn start_child_run(&self) -> Result<Run> {
+        if self.status.is_terminal() {
+            return Err(TrackerError::InvalidOperation(format!(
+                "Cannot start a child of {:?} run {}",
+                self.status, self.id
+            )));
+        }
+
+        let mut child = Run::new(self.experiment_id);
+        child.parent_run_id = Some(self.id);
+        Ok(child)
+    }
+
     pub fn log_metric(&mut self, name: impl Into<String>, value: f64) {
-        let timestamp = Utc::now();
-        self.metrics
-            .entry(name.into())
-            .or_default()
-            .push((timestamp, value));
+        self.push_metric(name.into(), value, None);
+    }
+
+    pub fn log_metric_at_step(&mut self, name: impl Into<String>, value: f64, step: u64) {
+        self.push_metric(name.into(), value, Some(step));
+    }
+
+    fn push_metric(&mut self, name: String, value: f64, step: Option<u64>) {
+        let point = MetricPoint {
+            run_id: self.id,
+            name: name.clone(),
+            value,
+            timestamp: Utc::now(),
+            step,
+            epoch: None,
+        };
+        self.metrics.entry(name).or_default().push(point);
+    }
+
+    /// Sets a parameter to an int, float, bool, string, list or nested map.
+    /// Maps are flattened into dotted keys, so setting `optimizer` to
+    /// `{"name": "adam", "lr": 0.01}` sets `optimizer.name` and
+    /// `optimizer.lr`. Keys inside maps may not contain `.`.
+    ///
+    /// Params are write-once: setting a key again to the same value is a
+    /// no-op, and to a different value fails with
+    /// [`TrackerError::ParamConflict`] without setting anything.
+    pub fn set_param(&mut self, key: impl Into<String>, value: impl Into<Value>) -> Result<()> {
+        self.merge_params(key.into(), value.into())
+    }
+
+    /// Records every field of a serializable config, e.g. a
+    /// `#[derive(Serialize)]` hyperparameter struct, as a parameter. Nested
+    /// structs and maps are flattened into dotted keys. As with
+    /// [`Run::set_param`], nothing is set if any field conflicts.
+    pub fn log_params_from<T: Serialize>(&mut self, params: &T) -> Result<()> {
+        let value = serde_json::to_value(params)
+            .map_err(|e| TrackerError::InvalidOperation(e.to_string()))?;
+        if !value.is_object() {
+            return Err(TrackerError::InvalidOperation(format!(
+                "params must serialize to a map of fields, not {}",
+                value
+            )));
+        }
+
+        self.merge_params(String::new(), value)
+    }
+
+    fn merge_params(&mut self, key: String, value: Value) -> Result<()> {
+        let mut flattened = HashMap::new();
+        flatten_param(&mut flattened, key, value)?;
+
+        for (key, value) in &flattened {
+            if let Some(existing) = self.params.get(key) {
+                if existing != value {
+                    return Err(param_conflict(self.id, key, existing, value));
+                }
+            }
+        }
+        self.params.extend(flattened);
+        Ok(())
     }
 
-    pub fn set_param(&mut self, key: impl Into<String>, value: impl Into<String>) {
-        self.params.insert(key.into(), value.into());
+    /// Rebuilds a config logged with [`Run::log_params_from`], nesting dotted
+    /// keys back into structs and maps.
+    pub fn params_into<T: DeserializeOwned>(&self) -> Result<T> {
+        let mut keys: Vec<&String> = self.params.keys().collect();
+        keys.sort();
+
+        let mut root = serde_json::Map::new();
+        for key in keys {
+            let segments: Vec<&str> = key.split('.').collect();
+            let (last, parents) = segments.split_last().unwrap_or((&"", &[]));
+
+            let mut map = &mut root;
+            for (depth, segment) in parents.iter().enumerate() {
+                let child = map
+                    .entry(segment.to_string())
+                    .or_insert_with(|| Value::Object(serde_json::Map::new()));
+                map = child.as_object_mut().ok_or_else(|| {
+                    TrackerError::InvalidOperation(format!(
+                        "param '{}' cannot be nested under '{}', which is not a map",
+                        key,
+                        segments[..=depth].join(".")
+                    ))
+                })?;
+            }
+            map.insert(last.to_string(), self.params[key].clone());
+        }
+
+        serde_json::from_value(Value::Object(root))
+            .map_err(|e| TrackerError::InvalidOperation(e.to_string()))
     }
 
     pub fn add_tag(&mut self, key: impl Into<String>, value: impl Into<String>) {
@@ -59,8 +224,95 @@ impl Run {
         self.artifacts.push(path.into());
     }
 
-    pub fn finish(&mut self, status: RunStatus) {
+    /// Moves the run to `status`, rejecting transitions the lifecycle does
+    /// not allow (e.g. leaving a terminal state).
+    pub fn transition(&mut self, status: RunStatus) -> Result<()> {
+        if !self.status.can_transition_to(status) {
+            return Err(TrackerError::InvalidOperation(format!(
+                "Invalid run transition from {:?} to {:?}",
+                self.status, status
+            )));
+        }
+
+        let now = Utc::now();
+        if self.status == RunStatus::Scheduled && status == RunStatus::Running {
+            self.start_time = now;
+        }
+        if status.is_terminal() {
+            self.end_time = Some(now);
+        }
+
         self.status = status;
-        self.end_time = Some(Utc::now());
+        self.transitions.push(RunTransition { status, at: now });
+        Ok(())
+    }
+
+    pub fn finish(&mut self, status: RunStatus) -> Result<()> {
+        if !status.is_terminal() {
+            return Err(TrackerError::InvalidOperation(format!(
+                "{:?} is not a terminal run status",
+                status
+            )));
+        }
+
+        self.transition(status)
+    }
+}
+
+pub(crate) fn param_conflict(
+    run_id: Uuid,
+    key: &str,
+    existing: &Value,
+    attempted: &Value,
+) -> TrackerError {
+    TrackerError::ParamConflict(format!(
+        "param '{}' of run {} is already {}, cannot set it to {}",
+        key, run_id, existing, attempted
+    ))
+}
+
+/// Flattens nested maps in `value` into dotted keys under `key`. Empty keys
+/// and map keys containing `.` are rejected, as their dotted key could not
+/// be told apart from a different nesting when the params are rebuilt.
+pub(crate) fn flatten_param(
+    params: &mut HashMap<String, Value>,
+    key: String,
+    value: Value,
+) -> Result<()> {
+    match value {
+        // A config without fields sets nothing.
+        Value::Object(map) if map.is_empty() && key.is_empty() => {}
+        Value::Object(map) if !map.is_empty() => {
+            for (child, value) in map {
+                if child.is_empty() || child.contains('.') {
+                    return Err(TrackerError::InvalidOperation(format!(
+                        "param key '{}'{} must be non-empty and not contain '.', \
+                        which separates nested keys",
+                        child,
+                        if key.is_empty() {
+                            String::new()
+                        } else {
+                            format!(" under '{}'", key)
+                        }
+                    )));
+                }
+                let key = if key.is_empty() {
+                    child
+                } else {
+                    format!("{}.{}", key, child)
+                };
+                flatten_param(params, key, value)?;
+            }
+        }
+        value if key.is_empty() => {
+            return Err(TrackerError::InvalidOperation(format!(
+                "param {} needs a non-empty key",
+                value
+            )));
+        }
+        value => {
+            params.insert(key, value);
+        }
     }
+    Ok(())
 }
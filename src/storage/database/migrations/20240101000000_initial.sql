@@ -1,4 +1,6 @@
-CREATE TABLE experiments (
+-- Databases created before migrations were tracked already contain these
+-- tables, so the baseline must be safe to apply on top of them.
+CREATE TABLE IF NOT EXISTS experiments (
     id BLOB PRIMARY KEY,
     name TEXT NOT NULL,
     description TEXT,
@@ -8,7 +10,7 @@ CREATE TABLE experiments (
     active_run BLOB
 );
 
-CREATE TABLE runs (
+CREATE TABLE IF NOT EXISTS runs (
     id BLOB PRIMARY KEY,
     experiment_id BLOB NOT NULL,
     status TEXT NOT NULL,
@@ -21,4 +23,4 @@ CREATE TABLE runs (
     FOREIGN KEY (experiment_id) REFERENCES experiments(id)
 );
 
-CREATE INDEX idx_runs_experiment_id ON runs(experiment_id);
\ No newline at end of file
+CREATE INDEX IF NOT EXISTS idx_runs_experiment_id ON runs(experiment_id);
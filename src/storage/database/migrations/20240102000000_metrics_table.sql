@@ -0,0 +1,18 @@
+CREATE TABLE metrics (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    run_id BLOB NOT NULL,
+    name TEXT NOT NULL,
+    step INTEGER,
+    value REAL NOT NULL,
+    timestamp TIMESTAMP NOT NULL
+);
+
+CREATE INDEX idx_metrics_run_name_timestamp ON metrics(run_id, name, timestamp);
+CREATE INDEX idx_metrics_run_name_step ON metrics(run_id, name, step);
+
+-- Move the per-run JSON blobs (`{"name": [[timestamp, value], ...]}`) into rows.
+INSERT INTO metrics (run_id, name, step, value, timestamp)
+SELECT runs.id, series.key, NULL, json_extract(point.value, '$[1]'), json_extract(point.value, '$[0]')
+FROM runs, json_each(runs.metrics) AS series, json_each(series.value) AS point;
+
+ALTER TABLE runs DROP COLUMN metrics;
@@ -0,0 +1 @@
+ALTER TABLE runs ADD COLUMN transitions TEXT NOT NULL DEFAULT '[]';
@@ -0,0 +1,16 @@
+ALTER TABLE experiments ADD COLUMN active_runs TEXT NOT NULL DEFAULT '[]';
+ALTER TABLE experiments ADD COLUMN exclusive INTEGER NOT NULL DEFAULT 0;
+
+-- Carry the single active run over as a one-element JSON array of its
+-- hyphenated UUID string.
+UPDATE experiments
+SET active_runs = json_array(lower(
+    substr(hex(active_run), 1, 8) || '-' ||
+    substr(hex(active_run), 9, 4) || '-' ||
+    substr(hex(active_run), 13, 4) || '-' ||
+    substr(hex(active_run), 17, 4) || '-' ||
+    substr(hex(active_run), 21, 12)
+))
+WHERE active_run IS NOT NULL;
+
+ALTER TABLE experiments DROP COLUMN active_run;
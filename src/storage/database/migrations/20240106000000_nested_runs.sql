@@ -0,0 +1,3 @@
+ALTER TABLE runs ADD COLUMN parent_run_id BLOB REFERENCES runs(id);
+
+CREATE INDEX idx_runs_parent_run_id ON runs(parent_run_id);
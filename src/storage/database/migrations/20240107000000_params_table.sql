@@ -0,0 +1,20 @@
+CREATE TABLE params (
+    run_id BLOB NOT NULL,
+    key TEXT NOT NULL,
+    -- The value's JSON encoding, so it keeps its type.
+    value TEXT NOT NULL,
+    UNIQUE (run_id, key)
+);
+
+-- Move the per-run JSON objects into rows. json_each yields booleans as 1/0,
+-- so name them explicitly.
+INSERT INTO params (run_id, key, value)
+SELECT runs.id, param.key,
+    CASE param.type
+        WHEN 'true' THEN 'true'
+        WHEN 'false' THEN 'false'
+        ELSE json_quote(param.value)
+    END
+FROM runs, json_each(runs.params) AS param;
+
+ALTER TABLE runs DROP COLUMN params;
@@ -0,0 +1,16 @@
+CREATE TABLE artifacts (
+    id BLOB PRIMARY KEY,
+    run_id BLOB NOT NULL,
+    -- Path relative to the run's artifacts, e.g. `checkpoints/model.pt`.
+    file_name TEXT NOT NULL,
+    name TEXT NOT NULL,
+    -- Where the storage backend keeps the content.
+    path TEXT NOT NULL,
+    content_hash TEXT NOT NULL,
+    size_bytes INTEGER NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    content_type TEXT,
+    description TEXT,
+    tags TEXT NOT NULL,
+    UNIQUE (run_id, file_name)
+);
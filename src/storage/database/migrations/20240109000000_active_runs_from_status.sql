@@ -0,0 +1,6 @@
+-- An experiment's active runs are its top-level runs that are `Running` or
+-- `Paused`, read from `runs.status`, so starting or finishing a run never
+-- rewrites the experiment row.
+ALTER TABLE experiments DROP COLUMN active_runs;
+
+CREATE INDEX idx_runs_experiment_status ON runs(experiment_id, status);
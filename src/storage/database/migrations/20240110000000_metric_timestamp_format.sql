@@ -0,0 +1,6 @@
+-- Points moved out of the per-run JSON blobs kept serde's `Z` suffix, while
+-- points written since carry the `+00:00` offset they are bound with. Use
+-- the latter throughout so that timestamps sort and compare as text.
+UPDATE metrics
+SET timestamp = substr(timestamp, 1, length(timestamp) - 1) || '+00:00'
+WHERE timestamp LIKE '%Z';
@@ -1,33 +1,96 @@
-use crate::{Experiment, Result, Run, TrackerError};
+mod query;
+mod schema;
+
+pub use query::{CompareOp, Direction, Field, Literal, OrderBy, RunFilter, RunQuery};
+
+use crate::artifacts::{Artifact, ArtifactMetadata};
+use crate::metrics::sqlite::insert_metrics;
+use crate::metrics::{MetricPoint, MetricStore, SqliteMetricStore};
+use crate::run::param_conflict;
+use crate::{Config, Experiment, Result, Run, RunStatus, TrackerError};
 use sqlx::{
-    sqlite::{SqlitePool, SqlitePoolOptions},
-    Row,
+    sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions, SqliteRow},
+    QueryBuilder, Row, Sqlite, Transaction,
 };
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::time::Duration;
 use uuid::Uuid;
 
+const RUN_COLUMNS: &str = "id, experiment_id, parent_run_id, status, tags, artifacts, \
+    start_time, end_time, transitions";
+
+// Three bound parameters per row keeps each INSERT well under SQLite's
+// host parameter limit.
+const PARAM_CHUNK_SIZE: usize = 1000;
+
+/// Most ids bound into one `IN (...)` list, so queries over many runs stay
+/// under SQLite's host parameter limit.
+pub(crate) const ID_CHUNK_SIZE: usize = 500;
+
 pub struct Database {
     pool: SqlitePool,
 }
 
 impl Database {
     pub async fn new(database_url: &str) -> Result<Self> {
+        Self::connect(database_url, 5, Duration::from_secs(30)).await
+    }
+
+    pub async fn from_config(config: &Config) -> Result<Self> {
+        Self::connect(
+            &config.database_url,
+            config.db_pool_size,
+            config.db_connection_timeout,
+        )
+        .await
+    }
+
+    async fn connect(database_url: &str, pool_size: u32, timeout: Duration) -> Result<Self> {
+        let options = SqliteConnectOptions::from_str(database_url)
+            .map_err(|e| TrackerError::Database(e.to_string()))?
+            .create_if_missing(true);
+
         let pool = SqlitePoolOptions::new()
-            .max_connections(5)
-            .connect(database_url)
+            .max_connections(pool_size)
+            .acquire_timeout(timeout)
+            .connect_with(options)
+            .await
+            .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+        schema::MIGRATOR
+            .run(&pool)
             .await
             .map_err(|e| TrackerError::Database(e.to_string()))?;
 
-        // Note: migrations should be run manually or via a setup script
         Ok(Self { pool })
     }
 
+    /// Returns the version of the most recently applied migration.
+    pub async fn schema_version(&self) -> Result<Option<i64>> {
+        sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations WHERE success = 1")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| TrackerError::Database(e.to_string()))
+    }
+
+    pub(crate) fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+
+    pub async fn close(&self) {
+        self.pool.close().await;
+    }
+
     pub async fn create_experiment(&self, experiment: &Experiment) -> Result<()> {
         let tags_json = serde_json::to_string(&experiment.tags)
             .map_err(|e| TrackerError::Database(e.to_string()))?;
 
         sqlx::query(
-            "INSERT INTO experiments (id, name, description, tags, created_at, updated_at)
-            VALUES (?, ?, ?, ?, ?, ?)",
+            "INSERT INTO experiments (
+                id, name, description, tags, created_at, updated_at, exclusive
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?)",
         )
         .bind(experiment.id)
         .bind(&experiment.name)
@@ -35,16 +98,48 @@ impl Database {
         .bind(&tags_json)
         .bind(experiment.created_at)
         .bind(experiment.updated_at)
+        .bind(experiment.exclusive)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Saves the experiment's name, description, tags and `exclusive` flag.
+    /// `active_runs` is not stored; it is read from the runs' statuses.
+    pub async fn update_experiment(&self, experiment: &Experiment) -> Result<()> {
+        let tags_json = serde_json::to_string(&experiment.tags)
+            .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+        let result = sqlx::query(
+            "UPDATE experiments
+            SET name = ?, description = ?, tags = ?, updated_at = ?, exclusive = ?
+            WHERE id = ?",
+        )
+        .bind(&experiment.name)
+        .bind(&experiment.description)
+        .bind(&tags_json)
+        .bind(experiment.updated_at)
+        .bind(experiment.exclusive)
+        .bind(experiment.id)
         .execute(&self.pool)
         .await
         .map_err(|e| TrackerError::Database(e.to_string()))?;
 
+        if result.rows_affected() == 0 {
+            return Err(TrackerError::NotFound(format!(
+                "Experiment {}",
+                experiment.id
+            )));
+        }
+
         Ok(())
     }
 
     pub async fn get_experiment(&self, id: Uuid) -> Result<Option<Experiment>> {
         let row = sqlx::query(
-            "SELECT id, name, description, tags, created_at, updated_at, active_run
+            "SELECT id, name, description, tags, created_at, updated_at, exclusive
             FROM experiments WHERE id = ?",
         )
         .bind(id)
@@ -52,73 +147,675 @@ impl Database {
         .await
         .map_err(|e| TrackerError::Database(e.to_string()))?;
 
-        if let Some(row) = row {
-            let tags: serde_json::Value = serde_json::from_str(
-                row.try_get("tags")
-                    .map_err(|e| TrackerError::Database(e.to_string()))?,
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        let mut experiments = [experiment_from_row(&row)?];
+        self.load_active_runs(&mut experiments).await?;
+        let [experiment] = experiments;
+        Ok(Some(experiment))
+    }
+
+    /// Lists every experiment, oldest first.
+    pub async fn list_experiments(&self) -> Result<Vec<Experiment>> {
+        let rows = sqlx::query(
+            "SELECT id, name, description, tags, created_at, updated_at, exclusive
+            FROM experiments ORDER BY created_at",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+        let mut experiments = rows
+            .iter()
+            .map(experiment_from_row)
+            .collect::<Result<Vec<_>>>()?;
+        self.load_active_runs(&mut experiments).await?;
+        Ok(experiments)
+    }
+
+    /// Sets `Experiment::active_runs` of `experiments` to their top-level
+    /// runs that are `Running` or `Paused`.
+    pub(crate) async fn load_active_runs(&self, experiments: &mut [Experiment]) -> Result<()> {
+        let ids: Vec<Uuid> = experiments.iter().map(|experiment| experiment.id).collect();
+        let mut by_experiment: HashMap<Uuid, &mut Experiment> = experiments
+            .iter_mut()
+            .map(|experiment| {
+                experiment.active_runs.clear();
+                (experiment.id, experiment)
+            })
+            .collect();
+        let [running, paused] = active_statuses()?;
+
+        for chunk in ids.chunks(ID_CHUNK_SIZE) {
+            let mut query: QueryBuilder<Sqlite> = QueryBuilder::new(
+                "SELECT experiment_id, id FROM runs WHERE parent_run_id IS NULL AND status IN (",
+            );
+            query
+                .push_bind(&running)
+                .push(", ")
+                .push_bind(&paused)
+                .push(") AND experiment_id IN (");
+            let mut bound = query.separated(", ");
+            for experiment_id in chunk {
+                bound.push_bind(*experiment_id);
+            }
+            query.push(")");
+
+            let rows = query
+                .build()
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+            for row in rows {
+                let experiment_id: Uuid = row
+                    .try_get("experiment_id")
+                    .map_err(|e| TrackerError::Database(e.to_string()))?;
+                if let Some(experiment) = by_experiment.get_mut(&experiment_id) {
+                    experiment.active_runs.insert(
+                        row.try_get("id")
+                            .map_err(|e| TrackerError::Database(e.to_string()))?,
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn create_run(&self, run: &mut Run) -> Result<()> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+        insert_run(&mut tx, run).await?;
+        insert_metrics(&mut tx, &unsaved_metrics(run)).await?;
+        tx.commit()
+            .await
+            .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+        mark_metrics_saved(run);
+        Ok(())
+    }
+
+    /// Creates `run` as a new active run of its experiment. An exclusive
+    /// experiment's other active runs are checked in the transaction that
+    /// inserts the run, so concurrent callers cannot both start one.
+    pub async fn start_run(&self, run: &mut Run) -> Result<()> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+        // Inserting first takes SQLite's write lock, so concurrent starts
+        // are serialized before the check below reads the runs table.
+        insert_run(&mut tx, run).await?;
+
+        let exclusive: Option<bool> =
+            sqlx::query_scalar("SELECT exclusive FROM experiments WHERE id = ?")
+                .bind(run.experiment_id)
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(|e| TrackerError::Database(e.to_string()))?;
+        let Some(exclusive) = exclusive else {
+            return Err(TrackerError::NotFound(format!(
+                "Experiment {}",
+                run.experiment_id
+            )));
+        };
+
+        if exclusive {
+            let [running, paused] = active_statuses()?;
+            let others: i64 = sqlx::query_scalar(
+                "SELECT COUNT(*) FROM runs
+                WHERE experiment_id = ? AND id != ? AND parent_run_id IS NULL
+                    AND status IN (?, ?)",
             )
+            .bind(run.experiment_id)
+            .bind(run.id)
+            .bind(running)
+            .bind(paused)
+            .fetch_one(&mut *tx)
+            .await
             .map_err(|e| TrackerError::Database(e.to_string()))?;
 
-            Ok(Some(Experiment {
-                id: row
-                    .try_get("id")
-                    .map_err(|e| TrackerError::Database(e.to_string()))?,
-                name: row
-                    .try_get("name")
-                    .map_err(|e| TrackerError::Database(e.to_string()))?,
-                description: row
-                    .try_get("description")
-                    .map_err(|e| TrackerError::Database(e.to_string()))?,
-                tags: serde_json::from_value(tags)
-                    .map_err(|e| TrackerError::Database(e.to_string()))?,
-                created_at: row
-                    .try_get("created_at")
-                    .map_err(|e| TrackerError::Database(e.to_string()))?,
-                updated_at: row
-                    .try_get("updated_at")
-                    .map_err(|e| TrackerError::Database(e.to_string()))?,
-                active_run: row
-                    .try_get("active_run")
-                    .map_err(|e| TrackerError::Database(e.to_string()))?,
-            }))
-        } else {
-            Ok(None)
+            if others > 0 {
+                return Err(TrackerError::InvalidOperation(
+                    "An active run already exists".to_string(),
+                ));
+            }
         }
+
+        insert_metrics(&mut tx, &unsaved_metrics(run)).await?;
+        tx.commit()
+            .await
+            .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+        mark_metrics_saved(run);
+        Ok(())
     }
 
-    pub async fn create_run(&self, run: &Run) -> Result<()> {
-        sqlx::query(
-            "INSERT INTO runs (
-                id, experiment_id, status, metrics, params, 
-                tags, artifacts, start_time, end_time
-            )
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    /// Saves the run's status, tags, artifacts and timing, and records any
+    /// params and metric points not stored yet, all in one transaction.
+    /// Stored params are never changed: a param whose
+    /// value differs from the stored one fails with
+    /// [`TrackerError::ParamConflict`] and nothing is saved.
+    pub async fn update_run(&self, run: &mut Run) -> Result<()> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+        let result = sqlx::query(
+            "UPDATE runs
+            SET status = ?, tags = ?, artifacts = ?,
+                start_time = ?, end_time = ?, transitions = ?
+            WHERE id = ?",
         )
-        .bind(run.id)
-        .bind(run.experiment_id)
         .bind(
             serde_json::to_string(&run.status)
                 .map_err(|e| TrackerError::Database(e.to_string()))?,
         )
+        .bind(serde_json::to_string(&run.tags).map_err(|e| TrackerError::Database(e.to_string()))?)
         .bind(
-            serde_json::to_string(&run.metrics)
+            serde_json::to_string(&run.artifacts)
                 .map_err(|e| TrackerError::Database(e.to_string()))?,
         )
+        .bind(run.start_time)
+        .bind(run.end_time)
         .bind(
-            serde_json::to_string(&run.params)
+            serde_json::to_string(&run.transitions)
                 .map_err(|e| TrackerError::Database(e.to_string()))?,
         )
-        .bind(serde_json::to_string(&run.tags).map_err(|e| TrackerError::Database(e.to_string()))?)
+        .bind(run.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(TrackerError::NotFound(format!("Run {}", run.id)));
+        }
+
+        insert_run_params(&mut tx, run).await?;
+        insert_metrics(&mut tx, &unsaved_metrics(run)).await?;
+        tx.commit()
+            .await
+            .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+        mark_metrics_saved(run);
+        Ok(())
+    }
+
+    pub async fn get_run(&self, id: Uuid) -> Result<Option<Run>> {
+        let row = sqlx::query(&format!("SELECT {RUN_COLUMNS} FROM runs WHERE id = ?"))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        let mut runs = vec![run_from_row(&row)?];
+        self.load_run_params(&mut runs).await?;
+        Ok(runs.pop())
+    }
+
+    pub async fn list_runs(&self, experiment_id: Uuid) -> Result<Vec<Run>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {RUN_COLUMNS} FROM runs WHERE experiment_id = ? ORDER BY start_time"
+        ))
+        .bind(experiment_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+        let mut runs = rows.iter().map(run_from_row).collect::<Result<Vec<_>>>()?;
+        self.load_run_params(&mut runs).await?;
+        Ok(runs)
+    }
+
+    /// Returns the top-level runs of `experiment_id` that are still active
+    /// (`Running` or `Paused`), most recently started first. Child runs are
+    /// reachable through their parent and do not occupy the experiment's
+    /// active slots.
+    pub async fn list_active_runs(&self, experiment_id: Uuid) -> Result<Vec<Run>> {
+        let [running, paused] = active_statuses()?;
+        let rows = sqlx::query(&format!(
+            "SELECT {RUN_COLUMNS} FROM runs
+            WHERE experiment_id = ? AND parent_run_id IS NULL AND status IN (?, ?)
+            ORDER BY start_time DESC"
+        ))
+        .bind(experiment_id)
+        .bind(running)
+        .bind(paused)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+        let mut runs = rows.iter().map(run_from_row).collect::<Result<Vec<_>>>()?;
+        self.load_run_params(&mut runs).await?;
+        Ok(runs)
+    }
+
+    pub async fn list_child_runs(&self, parent_run_id: Uuid) -> Result<Vec<Run>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {RUN_COLUMNS} FROM runs WHERE parent_run_id = ? ORDER BY start_time"
+        ))
+        .bind(parent_run_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+        let mut runs = rows.iter().map(run_from_row).collect::<Result<Vec<_>>>()?;
+        self.load_run_params(&mut runs).await?;
+        Ok(runs)
+    }
+
+    /// Returns every run below `run_id` in the run tree, at any depth.
+    pub async fn list_descendant_runs(&self, run_id: Uuid) -> Result<Vec<Run>> {
+        let rows = sqlx::query(&format!(
+            "WITH RECURSIVE descendants(id) AS (
+                SELECT id FROM runs WHERE parent_run_id = ?
+                UNION ALL
+                SELECT runs.id FROM runs JOIN descendants ON runs.parent_run_id = descendants.id
+            )
+            SELECT {RUN_COLUMNS} FROM runs
+            WHERE id IN (SELECT id FROM descendants)
+            ORDER BY start_time"
+        ))
+        .bind(run_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+        let mut runs = rows.iter().map(run_from_row).collect::<Result<Vec<_>>>()?;
+        self.load_run_params(&mut runs).await?;
+        Ok(runs)
+    }
+
+    /// Returns the runs matching `query`, ordered and paginated as it
+    /// specifies. Runs without an explicit ordering come newest first.
+    pub async fn search_runs(&self, query: &RunQuery) -> Result<Vec<Run>> {
+        let mut builder: QueryBuilder<Sqlite> =
+            QueryBuilder::new(format!("SELECT {RUN_COLUMNS} FROM runs"));
+        query.push_clauses(&mut builder)?;
+
+        let rows = builder
+            .build()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+        let mut runs = rows.iter().map(run_from_row).collect::<Result<Vec<_>>>()?;
+        self.load_run_params(&mut runs).await?;
+        Ok(runs)
+    }
+
+    /// Records an artifact's location, size and content hash, replacing any
+    /// earlier record of the same file in the run.
+    pub async fn record_artifact(&self, artifact: &Artifact) -> Result<()> {
+        let metadata = &artifact.metadata;
+        sqlx::query(
+            "INSERT INTO artifacts (
+                id, run_id, file_name, name, path, content_hash, size_bytes,
+                created_at, content_type, description, tags
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (run_id, file_name) DO UPDATE SET
+                id = excluded.id, name = excluded.name, path = excluded.path,
+                content_hash = excluded.content_hash, size_bytes = excluded.size_bytes,
+                created_at = excluded.created_at, content_type = excluded.content_type,
+                description = excluded.description, tags = excluded.tags",
+        )
+        .bind(artifact.id)
+        .bind(artifact.run_id)
+        .bind(&artifact.file_name)
+        .bind(&artifact.name)
+        .bind(&artifact.path)
+        .bind(&metadata.content_hash)
+        .bind(metadata.size_bytes as i64)
+        .bind(metadata.created_at)
+        .bind(&metadata.content_type)
+        .bind(&metadata.description)
         .bind(
-            serde_json::to_string(&run.artifacts)
+            serde_json::to_string(&metadata.tags)
                 .map_err(|e| TrackerError::Database(e.to_string()))?,
         )
-        .bind(run.start_time)
-        .bind(run.end_time)
         .execute(&self.pool)
         .await
         .map_err(|e| TrackerError::Database(e.to_string()))?;
 
         Ok(())
     }
+
+    /// Lists the artifacts recorded for a run, ordered by file name.
+    pub async fn list_artifacts(&self, run_id: Uuid) -> Result<Vec<Artifact>> {
+        let rows = sqlx::query(
+            "SELECT id, run_id, file_name, name, path, content_hash, size_bytes,
+                created_at, content_type, description, tags
+            FROM artifacts WHERE run_id = ? ORDER BY file_name",
+        )
+        .bind(run_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+        rows.iter().map(artifact_from_row).collect()
+    }
+
+    /// Reads a run like [`Database::get_run`], along with every point of
+    /// every metric it logged.
+    pub async fn get_run_with_metrics(&self, id: Uuid) -> Result<Option<Run>> {
+        let Some(run) = self.get_run(id).await? else {
+            return Ok(None);
+        };
+        let mut runs = [run];
+        self.load_run_metrics(&mut runs, &[]).await?;
+        let [run] = runs;
+        Ok(Some(run))
+    }
+
+    async fn load_run_params(&self, runs: &mut [Run]) -> Result<()> {
+        let ids: Vec<Uuid> = runs.iter().map(|run| run.id).collect();
+        let mut by_run: HashMap<Uuid, &mut Run> =
+            runs.iter_mut().map(|run| (run.id, run)).collect();
+
+        for chunk in ids.chunks(ID_CHUNK_SIZE) {
+            let mut query: QueryBuilder<Sqlite> =
+                QueryBuilder::new("SELECT run_id, key, value FROM params WHERE run_id IN (");
+            let mut bound = query.separated(", ");
+            for run_id in chunk {
+                bound.push_bind(*run_id);
+            }
+            query.push(")");
+
+            let rows = query
+                .build()
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+            for row in rows {
+                let run_id: Uuid = row
+                    .try_get("run_id")
+                    .map_err(|e| TrackerError::Database(e.to_string()))?;
+                if let Some(run) = by_run.get_mut(&run_id) {
+                    let key: String = row
+                        .try_get("key")
+                        .map_err(|e| TrackerError::Database(e.to_string()))?;
+                    run.params.insert(key, json_column(&row, "value")?);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fills `Run::metrics` of `runs` with the named series, or with every
+    /// series when `names` is empty. Runs are read without their metrics,
+    /// since a training history can be arbitrarily long; this is the opt-in.
+    pub async fn load_run_metrics(&self, runs: &mut [Run], names: &[&str]) -> Result<()> {
+        let ids: Vec<Uuid> = runs.iter().map(|run| run.id).collect();
+        let mut metrics = SqliteMetricStore::new(self)
+            .get_metrics_many(&ids, names)
+            .await?;
+
+        for run in runs.iter_mut() {
+            if let Some(series) = metrics.remove(&run.id) {
+                for (name, points) in series {
+                    run.saved_metrics.insert(name.clone(), points.len());
+                    run.metrics.insert(name, points);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The `status` values of runs that occupy an experiment's active slots.
+fn active_statuses() -> Result<[String; 2]> {
+    let status = |status: RunStatus| {
+        serde_json::to_string(&status).map_err(|e| TrackerError::Database(e.to_string()))
+    };
+    Ok([status(RunStatus::Running)?, status(RunStatus::Paused)?])
+}
+
+/// The points of `run.metrics` logged since the run was last saved, so
+/// saving the same run repeatedly never duplicates points. Points are
+/// tracked by their position in each series rather than by timestamp, so
+/// points logged within the same clock tick are all kept.
+fn unsaved_metrics(run: &Run) -> Vec<MetricPoint> {
+    run.metrics
+        .iter()
+        .flat_map(|(name, series)| {
+            let saved = run.saved_metrics.get(name).copied().unwrap_or_default();
+            series.iter().skip(saved).cloned()
+        })
+        .collect()
+}
+
+/// Records every point of `run.metrics` as saved, once the transaction
+/// holding them has committed.
+fn mark_metrics_saved(run: &mut Run) {
+    run.saved_metrics = run
+        .metrics
+        .iter()
+        .map(|(name, series)| (name.clone(), series.len()))
+        .collect();
+}
+
+/// Inserts `run` and its params, leaving its metrics to the caller.
+async fn insert_run(tx: &mut Transaction<'_, Sqlite>, run: &Run) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO runs (
+            id, experiment_id, parent_run_id, status,
+            tags, artifacts, start_time, end_time, transitions
+        )
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(run.id)
+    .bind(run.experiment_id)
+    .bind(run.parent_run_id)
+    .bind(serde_json::to_string(&run.status).map_err(|e| TrackerError::Database(e.to_string()))?)
+    .bind(serde_json::to_string(&run.tags).map_err(|e| TrackerError::Database(e.to_string()))?)
+    .bind(serde_json::to_string(&run.artifacts).map_err(|e| TrackerError::Database(e.to_string()))?)
+    .bind(run.start_time)
+    .bind(run.end_time)
+    .bind(
+        serde_json::to_string(&run.transitions)
+            .map_err(|e| TrackerError::Database(e.to_string()))?,
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+    insert_run_params(tx, run).await
+}
+
+fn experiment_from_row(row: &SqliteRow) -> Result<Experiment> {
+    let tags: serde_json::Value = serde_json::from_str(
+        row.try_get("tags")
+            .map_err(|e| TrackerError::Database(e.to_string()))?,
+    )
+    .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+    Ok(Experiment {
+        id: row
+            .try_get("id")
+            .map_err(|e| TrackerError::Database(e.to_string()))?,
+        name: row
+            .try_get("name")
+            .map_err(|e| TrackerError::Database(e.to_string()))?,
+        description: row
+            .try_get("description")
+            .map_err(|e| TrackerError::Database(e.to_string()))?,
+        tags: serde_json::from_value(tags).map_err(|e| TrackerError::Database(e.to_string()))?,
+        created_at: row
+            .try_get("created_at")
+            .map_err(|e| TrackerError::Database(e.to_string()))?,
+        updated_at: row
+            .try_get("updated_at")
+            .map_err(|e| TrackerError::Database(e.to_string()))?,
+        active_runs: HashSet::new(),
+        exclusive: row
+            .try_get("exclusive")
+            .map_err(|e| TrackerError::Database(e.to_string()))?,
+    })
+}
+
+fn run_from_row(row: &SqliteRow) -> Result<Run> {
+    Ok(Run {
+        id: row
+            .try_get("id")
+            .map_err(|e| TrackerError::Database(e.to_string()))?,
+        experiment_id: row
+            .try_get("experiment_id")
+            .map_err(|e| TrackerError::Database(e.to_string()))?,
+        parent_run_id: row
+            .try_get("parent_run_id")
+            .map_err(|e| TrackerError::Database(e.to_string()))?,
+        status: json_column(row, "status")?,
+        metrics: HashMap::new(),
+        params: HashMap::new(),
+        tags: json_column(row, "tags")?,
+        artifacts: json_column(row, "artifacts")?,
+        start_time: row
+            .try_get("start_time")
+            .map_err(|e| TrackerError::Database(e.to_string()))?,
+        end_time: row
+            .try_get("end_time")
+            .map_err(|e| TrackerError::Database(e.to_string()))?,
+        transitions: json_column(row, "transitions")?,
+        saved_metrics: HashMap::new(),
+    })
+}
+
+fn artifact_from_row(row: &SqliteRow) -> Result<Artifact> {
+    let size_bytes: i64 = row
+        .try_get("size_bytes")
+        .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+    Ok(Artifact {
+        id: row
+            .try_get("id")
+            .map_err(|e| TrackerError::Database(e.to_string()))?,
+        run_id: row
+            .try_get("run_id")
+            .map_err(|e| TrackerError::Database(e.to_string()))?,
+        name: row
+            .try_get("name")
+            .map_err(|e| TrackerError::Database(e.to_string()))?,
+        file_name: row
+            .try_get("file_name")
+            .map_err(|e| TrackerError::Database(e.to_string()))?,
+        path: row
+            .try_get("path")
+            .map_err(|e| TrackerError::Database(e.to_string()))?,
+        metadata: ArtifactMetadata {
+            content_hash: row
+                .try_get("content_hash")
+                .map_err(|e| TrackerError::Database(e.to_string()))?,
+            size_bytes: size_bytes as u64,
+            created_at: row
+                .try_get("created_at")
+                .map_err(|e| TrackerError::Database(e.to_string()))?,
+            content_type: row
+                .try_get("content_type")
+                .map_err(|e| TrackerError::Database(e.to_string()))?,
+            description: row
+                .try_get("description")
+                .map_err(|e| TrackerError::Database(e.to_string()))?,
+            tags: json_column(row, "tags")?,
+        },
+    })
+}
+
+/// Inserts the params of `run` that the `params` table does not hold yet,
+/// failing with [`TrackerError::ParamConflict`] if a stored param has a
+/// different value.
+async fn insert_run_params(tx: &mut Transaction<'_, Sqlite>, run: &Run) -> Result<()> {
+    let stored = stored_params(tx, run.id).await?;
+
+    let mut pending = Vec::new();
+    for (key, value) in &run.params {
+        match stored.get(key) {
+            Some(existing) if existing == value => {}
+            Some(existing) => return Err(param_conflict(run.id, key, existing, value)),
+            None => pending.push((
+                key,
+                serde_json::to_string(value).map_err(|e| TrackerError::Database(e.to_string()))?,
+            )),
+        }
+    }
+
+    let mut inserted = 0;
+    for chunk in pending.chunks(PARAM_CHUNK_SIZE) {
+        let mut query: QueryBuilder<Sqlite> =
+            QueryBuilder::new("INSERT INTO params (run_id, key, value) ");
+        query.push_values(chunk, |mut row, (key, value)| {
+            row.push_bind(run.id)
+                .push_bind(key.as_str())
+                .push_bind(value.as_str());
+        });
+        query.push(" ON CONFLICT (run_id, key) DO NOTHING");
+        inserted += query
+            .build()
+            .execute(&mut **tx)
+            .await
+            .map_err(|e| TrackerError::Database(e.to_string()))?
+            .rows_affected();
+    }
+
+    // Another writer stored some of these params after they were read. Its
+    // value wins; writing the same value is still a no-op.
+    if inserted < pending.len() as u64 {
+        let stored = stored_params(tx, run.id).await?;
+        for (key, _) in &pending {
+            let value = &run.params[*key];
+            match stored.get(*key) {
+                Some(existing) if existing != value => {
+                    return Err(param_conflict(run.id, key, existing, value))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn stored_params(
+    tx: &mut Transaction<'_, Sqlite>,
+    run_id: Uuid,
+) -> Result<HashMap<String, serde_json::Value>> {
+    let rows = sqlx::query("SELECT key, value FROM params WHERE run_id = ?")
+        .bind(run_id)
+        .fetch_all(&mut **tx)
+        .await
+        .map_err(|e| TrackerError::Database(e.to_string()))?;
+
+    let mut stored = HashMap::new();
+    for row in &rows {
+        let key: String = row
+            .try_get("key")
+            .map_err(|e| TrackerError::Database(e.to_string()))?;
+        stored.insert(key, json_column(row, "value")?);
+    }
+    Ok(stored)
+}
+
+fn json_column<T: serde::de::DeserializeOwned>(row: &SqliteRow, column: &str) -> Result<T> {
+    let raw: String = row
+        .try_get(column)
+        .map_err(|e| TrackerError::Database(e.to_string()))?;
+    serde_json::from_str(&raw).map_err(|e| TrackerError::Database(e.to_string()))
 }
@@ -0,0 +1,673 @@
+use crate::{Result, RunStatus, TrackerError};
+use chrono::{DateTime, Utc};
+use sqlx::{QueryBuilder, Sqlite};
+use std::fmt;
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// A run search: an optional filter expression plus ordering and pagination.
+///
+/// ```ignore
+/// let query = RunQuery::new()
+///     .with_filter(r#"metrics.val_loss < 0.3 and params.lr = "0.01" and status = Completed"#)?
+///     .with_order_by("metrics.val_loss asc")?
+///     .with_limit(10);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RunQuery {
+    pub experiment_id: Option<Uuid>,
+    pub filter: Option<RunFilter>,
+    pub order_by: Vec<OrderBy>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+impl RunQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_experiment(mut self, experiment_id: Uuid) -> Self {
+        self.experiment_id = Some(experiment_id);
+        self
+    }
+
+    pub fn with_filter(mut self, expression: &str) -> Result<Self> {
+        self.filter = Some(expression.parse()?);
+        Ok(self)
+    }
+
+    /// Parses a comma-separated list of `<field> [asc|desc]` terms.
+    pub fn with_order_by(mut self, spec: &str) -> Result<Self> {
+        self.order_by = OrderBy::parse_list(spec)?;
+        Ok(self)
+    }
+
+    pub fn with_limit(mut self, limit: u32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn with_offset(mut self, offset: u32) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// Appends the `WHERE`, `ORDER BY` and `LIMIT` clauses for a query over
+    /// the `runs` table.
+    pub(crate) fn push_clauses(&self, query: &mut QueryBuilder<'_, Sqlite>) -> Result<()> {
+        query.push(" WHERE 1 = 1");
+        if let Some(experiment_id) = self.experiment_id {
+            query
+                .push(" AND runs.experiment_id = ")
+                .push_bind(experiment_id);
+        }
+        if let Some(filter) = &self.filter {
+            query.push(" AND (");
+            filter.push_sql(query)?;
+            query.push(")");
+        }
+
+        query.push(" ORDER BY ");
+        for order in &self.order_by {
+            order.field.push_sql(query);
+            query.push(match order.direction {
+                Direction::Asc => " ASC NULLS LAST, ",
+                Direction::Desc => " DESC NULLS LAST, ",
+            });
+        }
+        query.push("runs.start_time DESC");
+
+        match (self.limit, self.offset) {
+            (Some(limit), offset) => {
+                query
+                    .push(" LIMIT ")
+                    .push_bind(limit as i64)
+                    .push(" OFFSET ")
+                    .push_bind(offset.unwrap_or(0) as i64);
+            }
+            (None, Some(offset)) => {
+                query.push(" LIMIT -1 OFFSET ").push_bind(offset as i64);
+            }
+            (None, None) => {}
+        }
+
+        Ok(())
+    }
+}
+
+/// A parsed filter expression.
+///
+/// Comparisons have the form `<field> <op> <value>` and can be combined with
+/// `and`, `or`, `not` and parentheses. Fields are `metrics.<name>` (the
+/// latest logged value), `params.<key>`, `tags.<key>`, `status`,
+/// `start_time` and `end_time`. Values are numbers, quoted strings, `true`
+/// and `false`, or bare words such as run statuses. Params keep the type
+/// they were logged with, so `params.lr > 1e-4` compares numbers and
+/// `params.augment = true` matches booleans. A quoted number such as
+/// `params.lr = "0.01"` matches the param whether it was logged as a number
+/// or as text.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunFilter {
+    Compare {
+        field: Field,
+        op: CompareOp,
+        value: Literal,
+    },
+    And(Box<RunFilter>, Box<RunFilter>),
+    Or(Box<RunFilter>, Box<RunFilter>),
+    Not(Box<RunFilter>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Field {
+    Metric(String),
+    Param(String),
+    Tag(String),
+    Status,
+    StartTime,
+    EndTime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Number(f64),
+    Text(String),
+    Bool(bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderBy {
+    pub field: Field,
+    pub direction: Direction,
+}
+
+impl FromStr for RunFilter {
+    type Err = TrackerError;
+
+    fn from_str(expression: &str) -> Result<Self> {
+        let mut parser = Parser::new(expression)?;
+        let filter = parser.parse_or()?;
+        parser.expect_end()?;
+        Ok(filter)
+    }
+}
+
+impl OrderBy {
+    pub fn parse_list(spec: &str) -> Result<Vec<OrderBy>> {
+        spec.split(',')
+            .map(str::trim)
+            .filter(|term| !term.is_empty())
+            .map(|term| {
+                let mut words = term.split_whitespace();
+                let field = Field::parse(words.next().unwrap_or_default())?;
+                let direction = match words.next().map(str::to_ascii_lowercase).as_deref() {
+                    None | Some("asc") => Direction::Asc,
+                    Some("desc") => Direction::Desc,
+                    Some(other) => {
+                        return Err(TrackerError::InvalidQuery(format!(
+                            "unknown sort direction '{}'",
+                            other
+                        )))
+                    }
+                };
+                if let Some(extra) = words.next() {
+                    return Err(TrackerError::InvalidQuery(format!(
+                        "unexpected '{}' in order by term '{}'",
+                        extra, term
+                    )));
+                }
+                Ok(OrderBy { field, direction })
+            })
+            .collect()
+    }
+}
+
+impl Field {
+    fn parse(name: &str) -> Result<Field> {
+        let field = match name.split_once('.') {
+            Some(("metrics" | "metric", key)) if !key.is_empty() => Field::Metric(key.to_string()),
+            Some(("params" | "param", key)) if !key.is_empty() => Field::Param(key.to_string()),
+            Some(("tags" | "tag", key)) if !key.is_empty() => Field::Tag(key.to_string()),
+            _ => match name {
+                "status" => Field::Status,
+                "start_time" => Field::StartTime,
+                "end_time" => Field::EndTime,
+                _ => {
+                    return Err(TrackerError::InvalidQuery(format!(
+                        "unknown field '{}'; expected metrics.<name>, params.<key>, \
+                         tags.<key>, status, start_time or end_time",
+                        name
+                    )))
+                }
+            },
+        };
+        Ok(field)
+    }
+
+    fn push_sql(&self, query: &mut QueryBuilder<'_, Sqlite>) {
+        match self {
+            Field::Metric(name) => {
+                query
+                    .push("(SELECT value FROM metrics WHERE metrics.run_id = runs.id AND metrics.name = ")
+                    .push_bind(name.clone())
+                    .push(" ORDER BY metrics.timestamp DESC, metrics.id DESC LIMIT 1)");
+            }
+            Field::Param(key) => push_param(query, key, "json_extract(params.value, '$')"),
+            Field::Tag(key) => {
+                query
+                    .push("json_extract(runs.tags, ")
+                    .push_bind(json_path(key))
+                    .push(")");
+            }
+            Field::Status => {
+                query.push("runs.status");
+            }
+            Field::StartTime => {
+                query.push("runs.start_time");
+            }
+            Field::EndTime => {
+                query.push("runs.end_time");
+            }
+        }
+    }
+}
+
+impl RunFilter {
+    fn push_sql(&self, query: &mut QueryBuilder<'_, Sqlite>) -> Result<()> {
+        match self {
+            RunFilter::And(lhs, rhs) | RunFilter::Or(lhs, rhs) => {
+                let joiner = if matches!(self, RunFilter::And(..)) {
+                    ") AND ("
+                } else {
+                    ") OR ("
+                };
+                query.push("(");
+                lhs.push_sql(query)?;
+                query.push(joiner);
+                rhs.push_sql(query)?;
+                query.push(")");
+            }
+            RunFilter::Not(inner) => {
+                query.push("NOT (");
+                inner.push_sql(query)?;
+                query.push(")");
+            }
+            RunFilter::Compare { field, op, value } => push_comparison(query, field, *op, value)?,
+        }
+        Ok(())
+    }
+}
+
+fn push_comparison(
+    query: &mut QueryBuilder<'_, Sqlite>,
+    field: &Field,
+    op: CompareOp,
+    value: &Literal,
+) -> Result<()> {
+    match (field, value) {
+        (Field::Metric(name), Literal::Text(_) | Literal::Bool(_)) => {
+            return Err(TrackerError::InvalidQuery(format!(
+                "metrics.{} must be compared with a number, not {}",
+                name, value
+            )));
+        }
+        (Field::Status, _) => {
+            if !matches!(op, CompareOp::Eq | CompareOp::Ne) {
+                return Err(TrackerError::InvalidQuery(
+                    "status only supports = and !=".to_string(),
+                ));
+            }
+            let status = parse_status(value)?;
+            field.push_sql(query);
+            query.push(op.as_sql()).push_bind(
+                serde_json::to_string(&status)
+                    .map_err(|e| TrackerError::Database(e.to_string()))?,
+            );
+        }
+        (Field::StartTime | Field::EndTime, _) => {
+            let Literal::Text(text) = value else {
+                return Err(TrackerError::InvalidQuery(
+                    "timestamps must be quoted RFC 3339 strings".to_string(),
+                ));
+            };
+            let timestamp = DateTime::parse_from_rfc3339(text)
+                .map_err(|e| TrackerError::InvalidQuery(format!("'{}': {}", text, e)))?
+                .with_timezone(&Utc);
+            field.push_sql(query);
+            query.push(op.as_sql()).push_bind(timestamp);
+        }
+        (Field::Param(_) | Field::Tag(_), Literal::Number(number)) => {
+            // Values logged as strings still compare numerically.
+            query.push("CAST(");
+            field.push_sql(query);
+            query.push(" AS REAL)").push(op.as_sql()).push_bind(*number);
+        }
+        (Field::Param(key), Literal::Bool(flag)) => {
+            if !matches!(op, CompareOp::Eq | CompareOp::Ne) {
+                return Err(TrackerError::InvalidQuery(
+                    "booleans only support = and !=".to_string(),
+                ));
+            }
+            // json_extract turns booleans into 1 and 0, so name them
+            // explicitly; values logged as "true" or "false" text match too.
+            query.push("(CASE ");
+            push_param(query, key, "json_type(params.value)");
+            query.push(" WHEN 'true' THEN 'true' WHEN 'false' THEN 'false' ELSE ");
+            field.push_sql(query);
+            query
+                .push(" END)")
+                .push(op.as_sql())
+                .push_bind(flag.to_string());
+        }
+        (Field::Param(key), Literal::Text(text)) if parse_number(text).is_some() => {
+            // A quoted number also matches the param logged as a number, so
+            // `params.lr = "0.01"` finds runs whichever way lr was logged.
+            let number = parse_number(text).unwrap_or_default();
+            query.push("(CASE WHEN ");
+            push_param(query, key, "json_type(params.value)");
+            query.push(" IN ('integer', 'real') THEN ");
+            field.push_sql(query);
+            query.push(op.as_sql()).push_bind(number).push(" ELSE ");
+            field.push_sql(query);
+            query
+                .push(op.as_sql())
+                .push_bind(text.clone())
+                .push(" END)");
+        }
+        (_, Literal::Number(number)) => {
+            field.push_sql(query);
+            query.push(op.as_sql()).push_bind(*number);
+        }
+        (_, Literal::Bool(flag)) => {
+            field.push_sql(query);
+            query.push(op.as_sql()).push_bind(flag.to_string());
+        }
+        (_, Literal::Text(text)) => {
+            field.push_sql(query);
+            query.push(op.as_sql()).push_bind(text.clone());
+        }
+    }
+    Ok(())
+}
+
+fn parse_number(text: &str) -> Option<f64> {
+    text.trim()
+        .parse()
+        .ok()
+        .filter(|number: &f64| number.is_finite())
+}
+
+fn parse_status(value: &Literal) -> Result<RunStatus> {
+    let Literal::Text(text) = value else {
+        return Err(TrackerError::InvalidQuery(
+            "status must be compared with a status name".to_string(),
+        ));
+    };
+    let status = [
+        RunStatus::Scheduled,
+        RunStatus::Running,
+        RunStatus::Paused,
+        RunStatus::Completed,
+        RunStatus::Failed,
+        RunStatus::Interrupted,
+        RunStatus::Killed,
+    ]
+    .into_iter()
+    .find(|status| format!("{:?}", status).eq_ignore_ascii_case(text));
+
+    status.ok_or_else(|| TrackerError::InvalidQuery(format!("unknown run status '{}'", text)))
+}
+
+/// Pushes a subquery selecting `expression` over the run's param `key`.
+fn push_param(query: &mut QueryBuilder<'_, Sqlite>, key: &str, expression: &str) {
+    query
+        .push("(SELECT ")
+        .push(expression)
+        .push(" FROM params WHERE params.run_id = runs.id AND params.key = ")
+        .push_bind(key.to_string())
+        .push(")");
+}
+
+fn json_path(key: &str) -> String {
+    format!("$.\"{}\"", key.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl CompareOp {
+    fn as_sql(self) -> &'static str {
+        match self {
+            CompareOp::Eq => " = ",
+            CompareOp::Ne => " != ",
+            CompareOp::Lt => " < ",
+            CompareOp::Le => " <= ",
+            CompareOp::Gt => " > ",
+            CompareOp::Ge => " >= ",
+        }
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Literal::Number(number) => write!(f, "{}", number),
+            Literal::Text(text) => write!(f, "'{}'", text),
+            Literal::Bool(flag) => write!(f, "{}", flag),
+        }
+    }
+}
+
+impl fmt::Display for CompareOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_sql().trim())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Text(String),
+    Number(f64),
+    Op(CompareOp),
+    LParen,
+    RParen,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '(' => {
+                tokens.push(Token::LParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                i += 1;
+            }
+            '=' | '!' | '<' | '>' => {
+                let next = chars.get(i + 1).copied();
+                let (op, width) = match (c, next) {
+                    ('=', Some('=')) => (CompareOp::Eq, 2),
+                    ('=', _) => (CompareOp::Eq, 1),
+                    ('!', Some('=')) => (CompareOp::Ne, 2),
+                    ('<', Some('>')) => (CompareOp::Ne, 2),
+                    ('<', Some('=')) => (CompareOp::Le, 2),
+                    ('<', _) => (CompareOp::Lt, 1),
+                    ('>', Some('=')) => (CompareOp::Ge, 2),
+                    ('>', _) => (CompareOp::Gt, 1),
+                    _ => {
+                        return Err(TrackerError::InvalidQuery(format!(
+                            "unexpected '{}' at position {}",
+                            c, i
+                        )))
+                    }
+                };
+                tokens.push(Token::Op(op));
+                i += width;
+            }
+            '"' | '\'' => {
+                let quote = c;
+                let mut text = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => {
+                            return Err(TrackerError::InvalidQuery(
+                                "unterminated string literal".to_string(),
+                            ))
+                        }
+                        Some('\\') if chars.get(i + 1).is_some() => {
+                            text.push(chars[i + 1]);
+                            i += 2;
+                        }
+                        Some(&ch) if ch == quote => {
+                            i += 1;
+                            break;
+                        }
+                        Some(&ch) => {
+                            text.push(ch);
+                            i += 1;
+                        }
+                    }
+                }
+                tokens.push(Token::Text(text));
+            }
+            c if c.is_ascii_digit()
+                || (matches!(c, '-' | '+' | '.')
+                    && chars
+                        .get(i + 1)
+                        .is_some_and(|n| n.is_ascii_digit() || *n == '.')) =>
+            {
+                let start = i;
+                i += 1;
+                while i < chars.len() {
+                    let ch = chars[i];
+                    let exponent_sign =
+                        matches!(ch, '-' | '+') && matches!(chars[i - 1], 'e' | 'E');
+                    if ch.is_ascii_digit() || matches!(ch, '.' | 'e' | 'E') || exponent_sign {
+                        i += 1;
+                    } else {
+                        break;
+                    }
+                }
+                let literal: String = chars[start..i].iter().collect();
+                let number = literal.parse().map_err(|_| {
+                    TrackerError::InvalidQuery(format!("invalid number '{}'", literal))
+                })?;
+                tokens.push(Token::Number(number));
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | '.' | '-' | '/'))
+                {
+                    i += 1;
+                }
+                tokens.push(Token::Word(chars[start..i].iter().collect()));
+            }
+            _ => {
+                return Err(TrackerError::InvalidQuery(format!(
+                    "unexpected '{}' at position {}",
+                    c, i
+                )))
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn new(input: &str) -> Result<Self> {
+        Ok(Self {
+            tokens: tokenize(input)?,
+            position: 0,
+        })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.position += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect_end(&self) -> Result<()> {
+        match self.peek() {
+            None => Ok(()),
+            Some(token) => Err(TrackerError::InvalidQuery(format!(
+                "unexpected {:?} after end of expression",
+                token
+            ))),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<RunFilter> {
+        let mut filter = self.parse_and()?;
+        while self.eat_keyword("or") {
+            filter = RunFilter::Or(Box::new(filter), Box::new(self.parse_and()?));
+        }
+        Ok(filter)
+    }
+
+    fn parse_and(&mut self) -> Result<RunFilter> {
+        let mut filter = self.parse_unary()?;
+        while self.eat_keyword("and") {
+            filter = RunFilter::And(Box::new(filter), Box::new(self.parse_unary()?));
+        }
+        Ok(filter)
+    }
+
+    fn parse_unary(&mut self) -> Result<RunFilter> {
+        if self.eat_keyword("not") {
+            return Ok(RunFilter::Not(Box::new(self.parse_unary()?)));
+        }
+
+        if self.peek() == Some(&Token::LParen) {
+            self.position += 1;
+            let filter = self.parse_or()?;
+            return match self.next() {
+                Some(Token::RParen) => Ok(filter),
+                _ => Err(TrackerError::InvalidQuery("missing ')'".to_string())),
+            };
+        }
+
+        self.parse_comparison()
+    }
+
+    fn parse_comparison(&mut self) -> Result<RunFilter> {
+        let field = match self.next() {
+            Some(Token::Word(word)) => Field::parse(&word)?,
+            other => {
+                return Err(TrackerError::InvalidQuery(format!(
+                    "expected a field, found {:?}",
+                    other
+                )))
+            }
+        };
+
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            other => {
+                return Err(TrackerError::InvalidQuery(format!(
+                    "expected a comparison operator, found {:?}",
+                    other
+                )))
+            }
+        };
+
+        let value = match self.next() {
+            Some(Token::Number(number)) => Literal::Number(number),
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case("true") => Literal::Bool(true),
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case("false") => Literal::Bool(false),
+            Some(Token::Text(text)) | Some(Token::Word(text)) => Literal::Text(text),
+            other => {
+                return Err(TrackerError::InvalidQuery(format!(
+                    "expected a value, found {:?}",
+                    other
+                )))
+            }
+        };
+
+        Ok(RunFilter::Compare { field, op, value })
+    }
+}
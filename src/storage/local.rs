@@ -43,25 +43,47 @@ impl Storage for LocalStorage {
             .map_err(|e| TrackerError::Storage(e.to_string()))
     }
 
+    /// Lists every file under the run's directory, including those in
+    /// subdirectories.
     async fn list_artifacts(&self, run_id: Uuid) -> Result<Vec<String>> {
-        let dir = self.root.join(run_id.to_string());
         let mut artifacts = Vec::new();
+        let mut dirs = vec![self.root.join(run_id.to_string())];
 
-        let mut entries = fs::read_dir(&dir)
-            .await
-            .map_err(|e| TrackerError::Storage(e.to_string()))?;
+        while let Some(dir) = dirs.pop() {
+            // A run that never stored an artifact has no directory.
+            let mut entries = match fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(TrackerError::Storage(e.to_string())),
+            };
 
-        while let Some(entry) = entries
-            .next_entry()
-            .await
-            .map_err(|e| TrackerError::Storage(e.to_string()))?
-        {
-            artifacts.push(entry.path().to_string_lossy().into_owned());
+            while let Some(entry) = entries
+                .next_entry()
+                .await
+                .map_err(|e| TrackerError::Storage(e.to_string()))?
+            {
+                let file_type = entry
+                    .file_type()
+                    .await
+                    .map_err(|e| TrackerError::Storage(e.to_string()))?;
+                if file_type.is_dir() {
+                    dirs.push(entry.path());
+                } else {
+                    artifacts.push(entry.path().to_string_lossy().into_owned());
+                }
+            }
         }
 
         Ok(artifacts)
     }
 
+    async fn artifact_size(&self, path: &str) -> Result<u64> {
+        let metadata = fs::metadata(path)
+            .await
+            .map_err(|e| TrackerError::Storage(e.to_string()))?;
+        Ok(metadata.len())
+    }
+
     async fn delete_artifact(&self, path: &str) -> Result<()> {
         fs::remove_file(path)
             .await
@@ -0,0 +1,74 @@
+use super::Storage;
+use crate::{Result, TrackerError};
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+/// Keeps artifacts in process memory. Useful for tests and for runs whose
+/// artifacts do not need to outlive the process.
+#[derive(Default)]
+pub struct InMemoryStorage {
+    artifacts: Mutex<BTreeMap<String, Vec<u8>>>,
+}
+
+impl InMemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn artifact_path(run_id: Uuid, name: &str) -> String {
+        format!("{}/{}", run_id, name)
+    }
+}
+
+#[async_trait]
+impl Storage for InMemoryStorage {
+    async fn store_artifact(&self, run_id: Uuid, name: &str, data: &[u8]) -> Result<String> {
+        let path = Self::artifact_path(run_id, name);
+        self.artifacts
+            .lock()
+            .unwrap()
+            .insert(path.clone(), data.to_vec());
+        Ok(path)
+    }
+
+    async fn get_artifact(&self, path: &str) -> Result<Vec<u8>> {
+        self.artifacts
+            .lock()
+            .unwrap()
+            .get(path)
+            .cloned()
+            .ok_or_else(|| TrackerError::NotFound(format!("Artifact {}", path)))
+    }
+
+    async fn list_artifacts(&self, run_id: Uuid) -> Result<Vec<String>> {
+        let prefix = format!("{}/", run_id);
+        Ok(self
+            .artifacts
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|path| path.starts_with(&prefix))
+            .cloned()
+            .collect())
+    }
+
+    async fn artifact_size(&self, path: &str) -> Result<u64> {
+        self.artifacts
+            .lock()
+            .unwrap()
+            .get(path)
+            .map(|data| data.len() as u64)
+            .ok_or_else(|| TrackerError::NotFound(format!("Artifact {}", path)))
+    }
+
+    async fn delete_artifact(&self, path: &str) -> Result<()> {
+        self.artifacts
+            .lock()
+            .unwrap()
+            .remove(path)
+            .map(|_| ())
+            .ok_or_else(|| TrackerError::NotFound(format!("Artifact {}", path)))
+    }
+}
@@ -1,9 +1,14 @@
 pub mod database;
 pub(crate) mod local;
+pub(crate) mod memory;
 pub(crate) mod s3;
 
-use crate::Result;
+use crate::{Result, TrackerError};
 use async_trait::async_trait;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::Mutex;
 use uuid::Uuid;
 
 #[async_trait]
@@ -11,9 +16,91 @@ pub trait Storage: Send + Sync {
     async fn store_artifact(&self, run_id: Uuid, name: &str, data: &[u8]) -> Result<String>;
     async fn get_artifact(&self, path: &str) -> Result<Vec<u8>>;
     async fn list_artifacts(&self, run_id: Uuid) -> Result<Vec<String>>;
+    /// Size of a stored artifact in bytes. The default reads the whole
+    /// artifact; backends that can look the size up without reading the
+    /// content should override it.
+    async fn artifact_size(&self, path: &str) -> Result<u64> {
+        Ok(self.get_artifact(path).await?.len() as u64)
+    }
     async fn delete_artifact(&self, path: &str) -> Result<()>;
 }
 
-pub use database::Database;
+/// An artifact storage backend, parsed from a URI:
+///
+/// - `file:///var/lib/mltracker` stores files under a local directory
+/// - `s3://bucket/prefix` stores objects in S3 (requires the `s3` feature)
+/// - `memory://` keeps artifacts in process memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageBackend {
+    Local(PathBuf),
+    S3 {
+        bucket: String,
+        prefix: Option<String>,
+    },
+    Memory,
+}
+
+impl StorageBackend {
+    /// Opens the backend for use by an `ArtifactManager`.
+    pub async fn open(&self) -> Result<Arc<Mutex<dyn Storage>>> {
+        let storage: Arc<Mutex<dyn Storage>> = match self {
+            StorageBackend::Local(root) => Arc::new(Mutex::new(LocalStorage::new(root))),
+            StorageBackend::Memory => Arc::new(Mutex::new(InMemoryStorage::new())),
+            #[cfg(feature = "s3")]
+            StorageBackend::S3 { bucket, prefix } => {
+                let mut storage = S3Storage::new(bucket.clone(), None).await?;
+                if let Some(prefix) = prefix {
+                    storage = storage.with_prefix(prefix.clone());
+                }
+                Arc::new(Mutex::new(storage))
+            }
+            #[cfg(not(feature = "s3"))]
+            StorageBackend::S3 { .. } => {
+                return Err(TrackerError::Storage(
+                    "S3 support not enabled; build with the `s3` feature".to_string(),
+                ))
+            }
+        };
+        Ok(storage)
+    }
+}
+
+impl FromStr for StorageBackend {
+    type Err = TrackerError;
+
+    fn from_str(uri: &str) -> Result<Self> {
+        let invalid = |reason: &str| {
+            TrackerError::InvalidConfig(format!("storage URI '{}': {}", uri, reason))
+        };
+
+        let (scheme, rest) = uri
+            .split_once("://")
+            .ok_or_else(|| invalid("expected file://, s3:// or memory://"))?;
+
+        match scheme {
+            "file" if !rest.is_empty() => Ok(StorageBackend::Local(PathBuf::from(rest))),
+            "file" => Err(invalid("missing path")),
+            "s3" => {
+                let (bucket, prefix) = match rest.split_once('/') {
+                    Some((bucket, prefix)) => (bucket, prefix.trim_matches('/')),
+                    None => (rest, ""),
+                };
+                if bucket.is_empty() {
+                    return Err(invalid("missing bucket"));
+                }
+                Ok(StorageBackend::S3 {
+                    bucket: bucket.to_string(),
+                    prefix: (!prefix.is_empty()).then(|| prefix.to_string()),
+                })
+            }
+            "memory" if rest.is_empty() => Ok(StorageBackend::Memory),
+            "memory" => Err(invalid("memory:// takes no path")),
+            _ => Err(invalid("unsupported scheme")),
+        }
+    }
+}
+
+pub use database::{Database, RunQuery};
 pub use local::LocalStorage;
+pub use memory::InMemoryStorage;
 pub use s3::S3Storage;
@@ -1,8 +1,13 @@
 #![allow(unused_imports)]
 
-use crate::Result;
+use super::Storage;
+use crate::{Result, TrackerError};
 use async_trait::async_trait;
 #[cfg(feature = "s3")]
+use aws_sdk_s3::config::{BehaviorVersion, Credentials, Region};
+#[cfg(feature = "s3")]
+use aws_sdk_s3::error::DisplayErrorContext;
+#[cfg(feature = "s3")]
 use aws_sdk_s3::{Client, Config};
 use uuid::Uuid;
 
@@ -10,28 +15,70 @@ use uuid::Uuid;
 pub struct S3Storage {
     client: Client,
     bucket: String,
+    prefix: Option<String>,
 }
 
 #[cfg(feature = "s3")]
 impl S3Storage {
+    /// Creates a client for `bucket`. The region defaults to `AWS_REGION`
+    /// (or `AWS_DEFAULT_REGION`); credentials are read from
+    /// `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN`,
+    /// and `AWS_ENDPOINT_URL` points the client at an S3-compatible service.
     pub async fn new(bucket: impl Into<String>, region: Option<String>) -> Result<Self> {
-        let config = Config::builder()
-            .region(region.map(aws_sdk_s3::Region::new))
-            .build();
-
-        let client = Client::from_conf(config);
+        let region = region
+            .or_else(|| std::env::var("AWS_REGION").ok())
+            .or_else(|| std::env::var("AWS_DEFAULT_REGION").ok());
+
+        let mut config = Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(region.map(Region::new));
+        if let (Ok(access_key), Ok(secret_key)) = (
+            std::env::var("AWS_ACCESS_KEY_ID"),
+            std::env::var("AWS_SECRET_ACCESS_KEY"),
+        ) {
+            let session_token = std::env::var("AWS_SESSION_TOKEN").ok();
+            config = config.credentials_provider(Credentials::new(
+                access_key,
+                secret_key,
+                session_token,
+                None,
+                "environment",
+            ));
+        }
+        if let Ok(endpoint) = std::env::var("AWS_ENDPOINT_URL") {
+            config = config.endpoint_url(endpoint).force_path_style(true);
+        }
 
         Ok(Self {
-            client,
+            client: Client::from_conf(config.build()),
             bucket: bucket.into(),
+            prefix: None,
         })
     }
 
+    /// Stores objects under `prefix/` instead of the bucket root.
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    fn run_prefix(&self, run_id: Uuid) -> String {
+        match &self.prefix {
+            Some(prefix) => format!("{}/{}/", prefix, run_id),
+            None => format!("{}/", run_id),
+        }
+    }
+
     fn object_key(&self, run_id: Uuid, name: &str) -> String {
-        format!("{}/{}", run_id, name)
+        format!("{}{}", self.run_prefix(run_id), name)
     }
 }
 
+#[cfg(feature = "s3")]
+fn s3_error(e: impl std::error::Error) -> TrackerError {
+    TrackerError::Storage(DisplayErrorContext(e).to_string())
+}
+
 #[cfg(feature = "s3")]
 #[async_trait]
 impl Storage for S3Storage {
@@ -44,7 +91,8 @@ impl Storage for S3Storage {
             .key(&key)
             .body(data.to_vec().into())
             .send()
-            .await?;
+            .await
+            .map_err(s3_error)?;
 
         Ok(key)
     }
@@ -56,28 +104,48 @@ impl Storage for S3Storage {
             .bucket(&self.bucket)
             .key(path)
             .send()
-            .await?;
+            .await
+            .map_err(s3_error)?;
 
-        Ok(output.body.collect().await?.to_vec())
+        let data = output.body.collect().await.map_err(s3_error)?;
+        Ok(data.to_vec())
     }
 
     async fn list_artifacts(&self, run_id: Uuid) -> Result<Vec<String>> {
-        let prefix = format!("{}/", run_id);
+        let prefix = self.run_prefix(run_id);
 
-        let objects = self
+        let mut pages = self
             .client
             .list_objects_v2()
             .bucket(&self.bucket)
             .prefix(&prefix)
+            .into_paginator()
+            .send();
+
+        let mut keys = Vec::new();
+        while let Some(page) = pages.next().await {
+            let page = page.map_err(s3_error)?;
+            keys.extend(
+                page.contents()
+                    .iter()
+                    .filter_map(|object| object.key().map(String::from)),
+            );
+        }
+
+        Ok(keys)
+    }
+
+    async fn artifact_size(&self, path: &str) -> Result<u64> {
+        let head = self
+            .client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(path)
             .send()
-            .await?;
-
-        Ok(objects
-            .contents()
-            .unwrap_or_default()
-            .iter()
-            .filter_map(|obj| obj.key().map(String::from))
-            .collect())
+            .await
+            .map_err(s3_error)?;
+
+        Ok(head.content_length().unwrap_or_default().max(0) as u64)
     }
 
     async fn delete_artifact(&self, path: &str) -> Result<()> {
@@ -86,7 +154,8 @@ impl Storage for S3Storage {
             .bucket(&self.bucket)
             .key(path)
             .send()
-            .await?;
+            .await
+            .map_err(s3_error)?;
 
         Ok(())
     }
@@ -1,12 +1,43 @@
-use crate::{Experiment, Result, TrackerError};
-use clap::{Parser, Subcommand};
-use console::{style, Term};
+use crate::run::flatten_param;
+use crate::ui::plot::Chart;
+use crate::{
+    Config, Downsample, Experiment, ExperimentTracker, MetricGoal, MetricRange, Result, Run,
+    RunQuery, TrackerError,
+};
+use clap::{Parser, Subcommand, ValueEnum};
+use console::{measure_text_width, pad_str, style, Alignment, Term};
 use dialoguer::{Input, Select};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 pub struct Cli {
+    #[arg(
+        long,
+        global = true,
+        env = "ML_TRACKER_CONFIG",
+        help = "TOML config file"
+    )]
+    config: Option<PathBuf>,
+
+    #[arg(long, global = true, env = "ML_TRACKER_DATABASE_URL")]
+    database_url: Option<String>,
+
+    #[arg(long, global = true, env = "ML_TRACKER_STORAGE_PATH")]
+    storage_path: Option<PathBuf>,
+
+    #[arg(
+        long,
+        global = true,
+        env = "ML_TRACKER_STORAGE_URI",
+        help = "Artifact storage URI (file://, s3://bucket/prefix or memory://)"
+    )]
+    storage_uri: Option<String>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -24,10 +55,30 @@ enum Commands {
     #[command(about = "List all experiments")]
     ListExperiments,
 
-    #[command(about = "Start a new run")]
+    #[command(
+        about = "Start a new run",
+        long_about = "Start a new run and print its id. Without --param, --params-file or \
+                      --tag, parameters are prompted for when attached to a terminal."
+    )]
     StartRun {
         #[arg(short, long)]
         experiment_id: Uuid,
+        #[arg(
+            short,
+            long = "param",
+            value_name = "KEY=VALUE",
+            value_parser = parse_param,
+            help = "VALUE is read as JSON when it parses, e.g. 0.01, true or [64,128], else as text"
+        )]
+        params: Vec<(String, Value)>,
+        #[arg(
+            long,
+            value_name = "FILE",
+            help = "JSON object of parameters, may be nested"
+        )]
+        params_file: Option<PathBuf>,
+        #[arg(short, long = "tag", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+        tags: Vec<(String, String)>,
     },
 
     #[command(about = "Show run details")]
@@ -41,10 +92,66 @@ enum Commands {
         #[arg(short, long)]
         run_id: Uuid,
     },
+
+    #[command(
+        about = "Compare runs side by side",
+        long_about = "Show the params, tags and artifacts that differ between runs, and each \
+                      metric's last and best value with the best run highlighted."
+    )]
+    Compare {
+        #[arg(short, long = "run-id", required = true, num_args = 1..)]
+        run_ids: Vec<Uuid>,
+    },
+
+    #[command(
+        about = "Plot a metric in the terminal",
+        long_about = "Draw a metric of one or more runs as a line chart, one colored line per run. \
+                      Long series are downsampled to the chart's width."
+    )]
+    Plot {
+        #[arg(short, long = "run-id", required = true, num_args = 1..)]
+        run_ids: Vec<Uuid>,
+        #[arg(short, long)]
+        metric: String,
+        #[arg(long, value_enum, default_value_t = PlotAxis::Step)]
+        x_axis: PlotAxis,
+        #[arg(long, help = "Chart width in characters [default: terminal width]")]
+        width: Option<usize>,
+        #[arg(long, default_value_t = 15, help = "Chart height in characters")]
+        height: usize,
+    },
+
+    #[command(about = "Search runs with a filter expression")]
+    SearchRuns {
+        #[arg(
+            short,
+            long,
+            help = "e.g. 'metrics.val_loss < 0.3 and params.lr = \"0.01\" and status = Completed'"
+        )]
+        filter: Option<String>,
+        #[arg(short, long)]
+        experiment_id: Option<Uuid>,
+        #[arg(short, long, help = "e.g. 'metrics.val_loss asc, start_time desc'")]
+        order_by: Option<String>,
+        #[arg(short, long)]
+        limit: Option<u32>,
+        #[arg(long)]
+        offset: Option<u32>,
+    },
+}
+
+/// What the x axis of `plot` shows.
+#[derive(Clone, Copy, ValueEnum)]
+enum PlotAxis {
+    /// Step of each point; points logged without a step are left out.
+    Step,
+    /// Seconds since the run's first point.
+    Time,
 }
 
 pub struct CliApp {
     term: Term,
+    config: Config,
 }
 
 impl From<std::io::Error> for TrackerError {
@@ -63,25 +170,109 @@ impl CliApp {
     pub fn new() -> Self {
         Self {
             term: Term::stdout(),
+            config: Config::default(),
         }
     }
 
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
     pub async fn run(&self) -> Result<()> {
-        let cli = Cli::parse();
+        self.execute(Cli::parse()).await
+    }
+
+    /// Runs an already parsed command line against the configured database
+    /// and artifact storage. Settings are layered over the app's `Config`:
+    /// the `--config` file, then `ML_TRACKER_*` variables, then
+    /// `--database-url` and `--storage-path`.
+    pub async fn execute(&self, cli: Cli) -> Result<()> {
+        let config = match &cli.config {
+            Some(path) => self.config.clone().merge_file(path)?,
+            None => self.config.clone(),
+        };
+        let mut config = config.merge_env()?;
+        if let Some(database_url) = cli.database_url {
+            config.database_url = database_url;
+        }
+        if let Some(storage_path) = cli.storage_path {
+            config.storage_path = storage_path;
+        }
+        if let Some(storage_uri) = cli.storage_uri {
+            config.storage_uri = Some(storage_uri);
+        }
 
-        match cli.command {
+        let tracker = ExperimentTracker::new(config.clone()).await?;
+        let result = match cli.command {
             Commands::CreateExperiment { name, description } => {
-                self.create_experiment(name, description).await
+                self.create_experiment(&tracker, name, description).await
             }
-            Commands::ListExperiments => self.list_experiments().await,
-            Commands::StartRun { experiment_id } => self.start_run(experiment_id).await,
-            Commands::ShowRun { run_id } => self.show_run(run_id).await,
-            Commands::ListArtifacts { run_id } => self.list_artifacts(run_id).await,
-        }
+            Commands::ListExperiments => self.list_experiments(&tracker).await,
+            Commands::StartRun {
+                experiment_id,
+                params,
+                params_file,
+                tags,
+            } => {
+                self.start_run(&tracker, experiment_id, params, params_file, tags)
+                    .await
+            }
+            Commands::ShowRun { run_id } => self.show_run(&tracker, run_id).await,
+            Commands::ListArtifacts { run_id } => self.list_artifacts(&tracker, run_id).await,
+            Commands::Compare { run_ids } => self.compare(&tracker, &run_ids).await,
+            Commands::Plot {
+                run_ids,
+                metric,
+                x_axis,
+                width,
+                height,
+            } => {
+                self.plot(&tracker, &run_ids, &metric, x_axis, width, height)
+                    .await
+            }
+            Commands::SearchRuns {
+                filter,
+                experiment_id,
+                order_by,
+                limit,
+                offset,
+            } => {
+                let mut query = RunQuery::new();
+                if let Some(filter) = filter {
+                    query = query.with_filter(&filter)?;
+                }
+                if let Some(experiment_id) = experiment_id {
+                    query = query.with_experiment(experiment_id);
+                }
+                if let Some(order_by) = order_by {
+                    query = query.with_order_by(&order_by)?;
+                }
+                if let Some(limit) = limit {
+                    query = query.with_limit(limit);
+                }
+                if let Some(offset) = offset {
+                    query = query.with_offset(offset);
+                }
+                self.search_runs(&tracker, query).await
+            }
+        };
+
+        tracker.shutdown().await?;
+        result
     }
 
-    async fn create_experiment(&self, name: String, description: Option<String>) -> Result<()> {
-        let experiment = Experiment::new(name).with_description(description.unwrap_or_default());
+    async fn create_experiment(
+        &self,
+        tracker: &ExperimentTracker,
+        name: String,
+        description: Option<String>,
+    ) -> Result<()> {
+        let mut experiment = Experiment::new(name);
+        if let Some(description) = description {
+            experiment = experiment.with_description(description);
+        }
+        let experiment = tracker.register_experiment(experiment).await?;
 
         self.term.write_line(&format!(
             "{} Created experiment {} (ID: {})",
@@ -93,13 +284,92 @@ impl CliApp {
         Ok(())
     }
 
-    async fn list_experiments(&self) -> Result<()> {
-        self.term.write_line("Experiments:")?;
-        self.term.write_line("------------")?;
+    async fn list_experiments(&self, tracker: &ExperimentTracker) -> Result<()> {
+        let experiments = tracker.list_experiments().await?;
+        if experiments.is_empty() {
+            self.term
+                .write_line(&style("No experiments found").dim().to_string())?;
+            return Ok(());
+        }
+
+        let rows: Vec<Vec<String>> = experiments
+            .iter()
+            .map(|experiment| {
+                vec![
+                    experiment.id.to_string(),
+                    experiment.name.clone(),
+                    experiment.active_runs.len().to_string(),
+                    experiment
+                        .created_at
+                        .format("%Y-%m-%d %H:%M:%S")
+                        .to_string(),
+                    experiment.description.clone().unwrap_or_default(),
+                ]
+            })
+            .collect();
+
+        self.write_table(&["ID", "NAME", "ACTIVE", "CREATED", "DESCRIPTION"], &rows)
+    }
+
+    async fn start_run(
+        &self,
+        tracker: &ExperimentTracker,
+        experiment_id: Uuid,
+        params: Vec<(String, Value)>,
+        params_file: Option<PathBuf>,
+        tags: Vec<(String, String)>,
+    ) -> Result<()> {
+        let mut experiment = tracker
+            .get_experiment(experiment_id)
+            .await?
+            .ok_or_else(|| TrackerError::NotFound(format!("Experiment {}", experiment_id)))?;
+
+        let interactive = params.is_empty()
+            && params_file.is_none()
+            && tags.is_empty()
+            && std::io::stdin().is_terminal();
+
+        // Flags win over the file, so a sweep can share one file and
+        // override single values. Params are write-once, so overrides are
+        // resolved on the flattened keys before any is set.
+        let mut given = match &params_file {
+            Some(path) => read_params_file(path)?,
+            None => Vec::new(),
+        };
+        given.extend(params);
+        if interactive {
+            given.extend(self.prompt_params()?);
+        }
+        let mut run_params = HashMap::new();
+        for (key, value) in given {
+            flatten_param(&mut run_params, key, value)?;
+        }
+
+        let mut run = Run::new(experiment.id);
+        for (key, value) in run_params {
+            run.set_param(key, value)?;
+        }
+        for (key, value) in tags {
+            run.add_tag(key, value);
+        }
+        let run = tracker.start_prepared_run(&mut experiment, run).await?;
+
+        if interactive {
+            self.term.write_line(&format!(
+                "{} Started run {} in {}",
+                style("✓").green(),
+                run.id,
+                style(&experiment.name).cyan()
+            ))?;
+        } else {
+            self.term.write_line(&run.id.to_string())?;
+        }
+
         Ok(())
     }
 
-    async fn start_run(&self, _experiment_id: Uuid) -> Result<()> {
+    fn prompt_params(&self) -> Result<Vec<(String, Value)>> {
+        let mut params = Vec::new();
         let input = Input::<String>::new();
         loop {
             let add_param = Select::new()
@@ -113,33 +383,415 @@ impl CliApp {
                 break;
             }
 
-            let _key = input
+            let key = input
                 .clone()
                 .with_prompt("Parameter name")
                 .interact_text()
                 .map_err(|e| TrackerError::InvalidOperation(e.to_string()))?;
 
-            let _value = input
+            let value = input
                 .clone()
                 .with_prompt("Parameter value")
                 .interact_text()
                 .map_err(|e| TrackerError::InvalidOperation(e.to_string()))?;
+
+            params.push((key, parse_param_value(&value)));
         }
 
-        Ok(())
+        Ok(params)
     }
 
-    async fn show_run(&self, run_id: Uuid) -> Result<()> {
+    async fn show_run(&self, tracker: &ExperimentTracker, run_id: Uuid) -> Result<()> {
+        let run = tracker
+            .get_run(run_id)
+            .await?
+            .ok_or_else(|| TrackerError::NotFound(format!("Run {}", run_id)))?;
+
         self.term
-            .write_line(&format!("Run Details (ID: {})", run_id))?;
+            .write_line(&format!("Run Details (ID: {})", run.id))?;
         self.term.write_line("------------")?;
-        Ok(())
+
+        let mut details = vec![
+            vec!["Experiment".to_string(), run.experiment_id.to_string()],
+            vec!["Status".to_string(), format!("{:?}", run.status)],
+            vec![
+                "Started".to_string(),
+                run.start_time.format("%Y-%m-%d %H:%M:%S").to_string(),
+            ],
+        ];
+        if let Some(end_time) = run.end_time {
+            details.push(vec![
+                "Ended".to_string(),
+                end_time.format("%Y-%m-%d %H:%M:%S").to_string(),
+            ]);
+        }
+        if let Some(parent_run_id) = run.parent_run_id {
+            details.push(vec!["Parent".to_string(), parent_run_id.to_string()]);
+        }
+        for row in &details {
+            self.term
+                .write_line(&format!("{:<12}{}", style(&row[0]).bold(), row[1]))?;
+        }
+
+        let mut params: Vec<Vec<String>> = run
+            .params
+            .iter()
+            .map(|(key, value)| vec![key.clone(), format_param(value)])
+            .collect();
+        params.sort();
+        self.write_section("Params", &["KEY", "VALUE"], &params)?;
+
+        let mut tags: Vec<Vec<String>> = run
+            .tags
+            .iter()
+            .map(|(key, value)| vec![key.clone(), value.clone()])
+            .collect();
+        tags.sort();
+        self.write_section("Tags", &["KEY", "VALUE"], &tags)?;
+
+        let store = tracker.metric_store();
+        let mut metrics = Vec::new();
+        for name in store.list_metric_keys(run.id).await? {
+            let Some(summary) = store
+                .summarize_metric(run.id, &name, MetricRange::all())
+                .await?
+            else {
+                continue;
+            };
+            metrics.push(vec![
+                name,
+                format!("{:.6}", summary.last),
+                format!("{:.6}", summary.min),
+                format!("{:.6}", summary.max),
+                format!("{:.6}", summary.mean),
+                summary.count.to_string(),
+            ]);
+        }
+        self.write_section(
+            "Metrics",
+            &["NAME", "LAST", "MIN", "MAX", "MEAN", "POINTS"],
+            &metrics,
+        )
     }
 
-    async fn list_artifacts(&self, run_id: Uuid) -> Result<()> {
+    /// Lists a run's artifacts with the size and hash recorded when they were
+    /// logged. Files found in storage without a record, e.g. written there
+    /// directly, get their size from the backend and no hash; no content is
+    /// read either way.
+    async fn list_artifacts(&self, tracker: &ExperimentTracker, run_id: Uuid) -> Result<()> {
+        let recorded = tracker.list_artifacts(run_id).await?;
+        let stored = tracker
+            .storage()
+            .lock()
+            .await
+            .list_artifacts(run_id)
+            .await?;
+
+        let mut rows: Vec<Vec<String>> = recorded
+            .iter()
+            .map(|artifact| {
+                vec![
+                    artifact.file_name.clone(),
+                    artifact.metadata.size_bytes.to_string(),
+                    artifact.metadata.content_hash.clone(),
+                ]
+            })
+            .collect();
+        for path in &stored {
+            if recorded.iter().any(|artifact| &artifact.path == path) {
+                continue;
+            }
+            let size = tracker.storage().lock().await.artifact_size(path).await?;
+            rows.push(vec![
+                relative_to_run(path, run_id).to_string(),
+                size.to_string(),
+                style("-").dim().to_string(),
+            ]);
+        }
+        rows.sort();
+
         self.term
             .write_line(&format!("Artifacts for Run {}", run_id))?;
         self.term.write_line("------------")?;
+        if rows.is_empty() {
+            self.term
+                .write_line(&style("No artifacts found").dim().to_string())?;
+            return Ok(());
+        }
+
+        self.write_table(&["NAME", "SIZE (BYTES)", "BLAKE3"], &rows)
+    }
+
+    async fn compare(&self, tracker: &ExperimentTracker, run_ids: &[Uuid]) -> Result<()> {
+        let comparison = tracker.compare_runs(run_ids).await?;
+
+        self.term
+            .write_line(&format!("Comparing {} runs", run_ids.len()))?;
+        self.term.write_line("------------")?;
+        for (index, run_id) in comparison.run_ids.iter().enumerate() {
+            self.term.write_line(&format!(
+                "{:<4}{}",
+                style(format!("#{}", index + 1)).bold(),
+                run_id
+            ))?;
+        }
+
+        let columns: Vec<String> = (1..=run_ids.len()).map(|n| format!("#{}", n)).collect();
+        let headers = |first: &'static [&'static str]| -> Vec<&str> {
+            first
+                .iter()
+                .copied()
+                .chain(columns.iter().map(String::as_str))
+                .collect()
+        };
+        let missing = || style("-").dim().to_string();
+
+        let params: Vec<Vec<String>> = comparison
+            .params
+            .iter()
+            .map(|(key, values)| {
+                let cells = values.iter().map(|value| match value {
+                    Some(value) => format_param(value),
+                    None => missing(),
+                });
+                std::iter::once(key.clone()).chain(cells).collect()
+            })
+            .collect();
+        self.write_section("Params", &headers(&["KEY"]), &params)?;
+
+        let tags: Vec<Vec<String>> = comparison
+            .tags
+            .iter()
+            .map(|(key, values)| {
+                let cells = values
+                    .iter()
+                    .map(|value| value.clone().unwrap_or_else(missing));
+                std::iter::once(key.clone()).chain(cells).collect()
+            })
+            .collect();
+        self.write_section("Tags", &headers(&["KEY"]), &tags)?;
+
+        let mut metrics = Vec::new();
+        for (name, metric) in &comparison.metrics {
+            let arrow = match metric.goal {
+                MetricGoal::Minimize => "↓",
+                MetricGoal::Maximize => "↑",
+            };
+            let format_value = |value: &Option<f64>| match value {
+                Some(value) => format!("{:.6}", value),
+                None => missing(),
+            };
+
+            let mut last = vec![format!("{} {}", name, arrow), "last".to_string()];
+            last.extend(metric.last.iter().map(format_value));
+            let mut best = vec![String::new(), "best".to_string()];
+            best.extend(metric.best.iter().enumerate().map(|(index, value)| {
+                let cell = format_value(value);
+                if metric.best_run == Some(index) {
+                    style(cell).green().bold().to_string()
+                } else {
+                    cell
+                }
+            }));
+            metrics.push(last);
+            metrics.push(best);
+        }
+        self.write_section("Metrics", &headers(&["METRIC", "VALUE"]), &metrics)?;
+
+        let artifacts: Vec<Vec<String>> = comparison
+            .artifacts
+            .iter()
+            .map(|(name, hashes)| {
+                let cells = hashes.iter().map(|hash| match hash {
+                    Some(hash) => hash.get(..12).unwrap_or(hash).to_string(),
+                    None => missing(),
+                });
+                std::iter::once(name.clone()).chain(cells).collect()
+            })
+            .collect();
+        self.write_section("Artifacts (BLAKE3)", &headers(&["NAME"]), &artifacts)
+    }
+
+    async fn plot(
+        &self,
+        tracker: &ExperimentTracker,
+        run_ids: &[Uuid],
+        metric: &str,
+        x_axis: PlotAxis,
+        width: Option<usize>,
+        height: usize,
+    ) -> Result<()> {
+        let width = width.unwrap_or_else(|| {
+            let (_, columns) = self.term.size();
+            (columns as usize).saturating_sub(12).clamp(20, 160)
+        });
+        let (range, x_label) = match x_axis {
+            PlotAxis::Step => (MetricRange::steps(..), "step"),
+            PlotAxis::Time => (MetricRange::all(), "seconds"),
+        };
+
+        let store = tracker.metric_store();
+        let mut chart = Chart::new(width, height).with_x_label(x_label);
+        let mut plotted = 0;
+        for (index, run_id) in run_ids.iter().enumerate() {
+            if tracker.get_run(*run_id).await?.is_none() {
+                return Err(TrackerError::NotFound(format!("Run {}", run_id)));
+            }
+            // Each character cell is two braille dots wide.
+            let points = store
+                .downsample_metric(*run_id, metric, range, width * 2, Downsample::Lttb)
+                .await?;
+            let start = points.first().map(|point| point.timestamp);
+            let xy: Vec<(f64, f64)> = points
+                .iter()
+                .map(|point| {
+                    let x = match (x_axis, start) {
+                        (PlotAxis::Step, _) => point.step.unwrap_or_default() as f64,
+                        (PlotAxis::Time, Some(start)) => {
+                            (point.timestamp - start).num_milliseconds() as f64 / 1000.0
+                        }
+                        (PlotAxis::Time, None) => 0.0,
+                    };
+                    (x, point.value)
+                })
+                .collect();
+
+            let summary = match points.last() {
+                Some(last) => format!("last {:.6}", last.value),
+                None => "no points".to_string(),
+            };
+            if !xy.is_empty() {
+                plotted += 1;
+            }
+            chart.add_series(format!("#{} {}  {}", index + 1, run_id, summary), xy);
+        }
+
+        if plotted == 0 {
+            return Err(TrackerError::NotFound(format!(
+                "No {}points of metric '{}' in the given runs",
+                match x_axis {
+                    PlotAxis::Step => "stepped ",
+                    PlotAxis::Time => "",
+                },
+                metric
+            )));
+        }
+
+        self.term.write_line(&style(metric).bold().to_string())?;
+        for line in chart.render() {
+            self.term.write_line(&line)?;
+        }
+
+        Ok(())
+    }
+
+    async fn search_runs(&self, tracker: &ExperimentTracker, query: RunQuery) -> Result<()> {
+        let runs = tracker.search_runs(&query).await?;
+
+        let rows: Vec<Vec<String>> = runs
+            .iter()
+            .map(|run| {
+                vec![
+                    run.id.to_string(),
+                    format!("{:?}", run.status),
+                    run.start_time.format("%Y-%m-%d %H:%M:%S").to_string(),
+                    run.experiment_id.to_string(),
+                ]
+            })
+            .collect();
+        self.write_table(&["RUN ID", "STATUS", "STARTED", "EXPERIMENT ID"], &rows)?;
+        self.term
+            .write_line(&format!("{} run(s) matched", runs.len()))?;
+
+        Ok(())
+    }
+
+    fn write_section(&self, title: &str, headers: &[&str], rows: &[Vec<String>]) -> Result<()> {
+        self.term.write_line("")?;
+        self.term
+            .write_line(&style(title).underlined().to_string())?;
+        if rows.is_empty() {
+            self.term.write_line(&style("(none)").dim().to_string())?;
+            return Ok(());
+        }
+        self.write_table(headers, rows)
+    }
+
+    /// Prints `rows` as left-aligned columns under bold `headers`. Cells may
+    /// be styled; escape codes do not count towards column widths.
+    fn write_table(&self, headers: &[&str], rows: &[Vec<String>]) -> Result<()> {
+        let mut widths: Vec<usize> = headers
+            .iter()
+            .map(|header| measure_text_width(header))
+            .collect();
+        for row in rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(measure_text_width(cell));
+            }
+        }
+
+        let format_row = |cells: Vec<String>| {
+            cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| pad_str(cell, *width, Alignment::Left, None).into_owned())
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        };
+
+        let header = format_row(headers.iter().map(|h| h.to_string()).collect());
+        self.term.write_line(&style(header).bold().to_string())?;
+        for row in rows {
+            self.term.write_line(&format_row(row.clone()))?;
+        }
+
         Ok(())
     }
 }
+
+/// The part of a storage path below the run's directory or prefix.
+fn relative_to_run(path: &str, run_id: Uuid) -> &str {
+    match path.split_once(&run_id.to_string()) {
+        Some((_, rest)) => rest.trim_start_matches(['/', '\\']),
+        None => path,
+    }
+}
+
+fn parse_key_value(arg: &str) -> std::result::Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected KEY=VALUE, got '{}'", arg)),
+    }
+}
+
+fn parse_param(arg: &str) -> std::result::Result<(String, Value), String> {
+    let (key, value) = parse_key_value(arg)?;
+    Ok((key, parse_param_value(&value)))
+}
+
+/// Reads `value` as JSON so that numbers, booleans and lists keep their
+/// type, falling back to plain text. Quote it (`'"32"'`) to keep a number as
+/// text.
+fn parse_param_value(value: &str) -> Value {
+    serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()))
+}
+
+fn format_param(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Reads a JSON object of parameters. Nested objects are flattened into
+/// dotted keys when the params are set on the run.
+fn read_params_file(path: &Path) -> Result<Vec<(String, Value)>> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| TrackerError::InvalidOperation(format!("{}: {}", path.display(), e)))?;
+    let params: serde_json::Map<String, Value> = serde_json::from_str(&contents)
+        .map_err(|e| TrackerError::InvalidOperation(format!("{}: {}", path.display(), e)))?;
+
+    Ok(params.into_iter().collect())
+}
@@ -0,0 +1,230 @@
+use console::{style, Color};
+
+/// Colors given to series in the order they are added.
+const PALETTE: [Color; 6] = [
+    Color::Cyan,
+    Color::Yellow,
+    Color::Green,
+    Color::Magenta,
+    Color::Blue,
+    Color::Red,
+];
+
+/// A line chart drawn with Unicode braille characters, so each character
+/// cell holds a 2x4 grid of dots.
+///
+/// ```ignore
+/// let mut chart = Chart::new(60, 15).with_x_label("step");
+/// chart.add_series("run #1", points);
+/// for line in chart.render() {
+///     println!("{}", line);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Chart {
+    width: usize,
+    height: usize,
+    x_label: Option<String>,
+    series: Vec<Series>,
+}
+
+#[derive(Debug, Clone)]
+struct Series {
+    label: String,
+    points: Vec<(f64, f64)>,
+}
+
+impl Chart {
+    /// A chart whose plot area is `width` by `height` characters, not
+    /// counting axes, labels and legend.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width: width.max(1),
+            height: height.max(1),
+            x_label: None,
+            series: Vec::new(),
+        }
+    }
+
+    pub fn with_x_label(mut self, x_label: impl Into<String>) -> Self {
+        self.x_label = Some(x_label.into());
+        self
+    }
+
+    /// Adds a series of `(x, y)` points, drawn as a line in the order given.
+    /// Non-finite values are skipped.
+    pub fn add_series(&mut self, label: impl Into<String>, points: Vec<(f64, f64)>) {
+        self.series.push(Series {
+            label: label.into(),
+            points: points
+                .into_iter()
+                .filter(|(x, y)| x.is_finite() && y.is_finite())
+                .collect(),
+        });
+    }
+
+    /// Renders the chart, its axes and a legend line per series. Series are
+    /// colored when the terminal supports it.
+    pub fn render(&self) -> Vec<String> {
+        let points = || self.series.iter().flat_map(|series| &series.points);
+        let (x_min, x_max) = padded_bounds(points().map(|(x, _)| *x));
+        let (y_min, y_max) = padded_bounds(points().map(|(_, y)| *y));
+
+        let dots_x = self.width * 2;
+        let dots_y = self.height * 4;
+        let to_dot = |(x, y): (f64, f64)| {
+            let column = (x - x_min) / (x_max - x_min) * (dots_x - 1) as f64;
+            let row = (y_max - y) / (y_max - y_min) * (dots_y - 1) as f64;
+            (column.round() as i64, row.round() as i64)
+        };
+
+        // Braille dots per cell, and the series that last drew in it.
+        let mut cells = vec![vec![(0u8, None::<usize>); self.width]; self.height];
+        for (index, series) in self.series.iter().enumerate() {
+            let dots: Vec<(i64, i64)> = series.points.iter().copied().map(to_dot).collect();
+            let mut plot = |(x, y): (i64, i64)| {
+                let cell = &mut cells[y as usize / 4][x as usize / 2];
+                cell.0 |= braille_bit(x as usize % 2, y as usize % 4);
+                cell.1 = Some(index);
+            };
+            match dots.as_slice() {
+                [] => {}
+                [dot] => plot(*dot),
+                dots => {
+                    for pair in dots.windows(2) {
+                        draw_line(pair[0], pair[1], &mut plot);
+                    }
+                }
+            }
+        }
+
+        let y_labels = [
+            (0, format_tick(y_max)),
+            (self.height / 2, format_tick((y_min + y_max) / 2.0)),
+            (self.height - 1, format_tick(y_min)),
+        ];
+        let label_width = y_labels
+            .iter()
+            .map(|(_, label)| label.chars().count())
+            .max()
+            .unwrap_or_default();
+
+        let mut lines = Vec::with_capacity(self.height + 3 + self.series.len());
+        for (row, row_cells) in cells.iter().enumerate() {
+            let (label, axis) = match y_labels.iter().find(|(labeled, _)| *labeled == row) {
+                Some((_, label)) => (label.as_str(), '┤'),
+                None => ("", '│'),
+            };
+            let plot: String = row_cells
+                .iter()
+                .map(|(bits, series)| {
+                    let c = char::from_u32(0x2800 + *bits as u32).unwrap_or(' ');
+                    match series {
+                        Some(index) => style(c).fg(color(*index)).to_string(),
+                        None => c.to_string(),
+                    }
+                })
+                .collect();
+            lines.push(format!("{:>label_width$} {}{}", label, axis, plot));
+        }
+        lines.push(format!("{:label_width$} └{}", "", "─".repeat(self.width)));
+
+        let (first, last) = (format_tick(x_min), format_tick(x_max));
+        let gap = (self.width + 1).saturating_sub(first.chars().count() + last.chars().count());
+        lines.push(format!(
+            "{:label_width$} {}{}{}",
+            "",
+            first,
+            " ".repeat(gap.max(1)),
+            last
+        ));
+        if let Some(x_label) = &self.x_label {
+            let line = format!(
+                "{:label_width$} {:^width$}",
+                "",
+                x_label,
+                width = self.width + 1
+            );
+            lines.push(line.trim_end().to_string());
+        }
+
+        for (index, series) in self.series.iter().enumerate() {
+            lines.push(format!(
+                "{:label_width$} {} {}",
+                "",
+                style("━━").fg(color(index)),
+                series.label
+            ));
+        }
+
+        lines
+    }
+}
+
+fn color(series: usize) -> Color {
+    PALETTE[series % PALETTE.len()]
+}
+
+/// The bit of a braille character for the dot at `column` (0-1) and `row`
+/// (0-3) of its cell.
+fn braille_bit(column: usize, row: usize) -> u8 {
+    const BITS: [[u8; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+    BITS[column][row]
+}
+
+/// Calls `plot` for every dot on the line from `from` to `to` (Bresenham).
+fn draw_line(from: (i64, i64), to: (i64, i64), plot: &mut impl FnMut((i64, i64))) {
+    let (mut x, mut y) = from;
+    let dx = (to.0 - x).abs();
+    let dy = -(to.1 - y).abs();
+    let step_x = if x < to.0 { 1 } else { -1 };
+    let step_y = if y < to.1 { 1 } else { -1 };
+    let mut error = dx + dy;
+
+    loop {
+        plot((x, y));
+        if (x, y) == to {
+            break;
+        }
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+}
+
+/// The smallest and largest of `values`, widened when they are equal so the
+/// axis still has a span.
+fn padded_bounds(values: impl Iterator<Item = f64>) -> (f64, f64) {
+    let (min, max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| {
+        (min.min(value), max.max(value))
+    });
+    if min > max {
+        (0.0, 1.0)
+    } else if min == max {
+        let pad = if min == 0.0 { 1.0 } else { min.abs() * 0.1 };
+        (min - pad, max + pad)
+    } else {
+        (min, max)
+    }
+}
+
+/// Formats an axis label: plain decimals with up to four places, or
+/// scientific notation for very large or small magnitudes.
+fn format_tick(value: f64) -> String {
+    let magnitude = value.abs();
+    if magnitude >= 1e5 || (magnitude < 1e-3 && magnitude > 0.0) {
+        return format!("{:.2e}", value);
+    }
+    let text = format!("{:.4}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    match text {
+        "-0" => "0".to_string(),
+        text => text.to_string(),
+    }
+}
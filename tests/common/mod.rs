@@ -1,52 +1,1780 @@
-use ml_tracker::{Config, ExperimentTracker, Result, RunStatus};
+use clap::Parser;
+use ml_tracker::ui::cli::{Cli, CliApp};
+use ml_tracker::{
+    Config, Downsample, ExperimentTracker, InMemoryMetricStore, InMemoryStorage, LocalStorage,
+    MetricLogger, MetricLoggerConfig, MetricPoint, MetricRange, MetricStore, Result, RunQuery,
+    RunStatus, Storage, StorageBackend, TrackerError,
+};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+
+pub struct TestContext {
+    pub tracker: ExperimentTracker,
+    pub config: Config,
+    _dir: TempDir,
+}
+
+impl TestContext {
+    pub async fn new() -> Result<Self> {
+        let dir = tempfile::tempdir()?;
+        let config = Config {
+            storage_path: dir.path().join("artifacts"),
+            database_url: format!("sqlite:{}", dir.path().join("experiments.db").display()),
+            ..Config::default()
+        };
+        let tracker = ExperimentTracker::new(config.clone()).await?;
+
+        Ok(Self {
+            tracker,
+            config,
+            _dir: dir,
+        })
+    }
+}
 
 #[tokio::test]
 async fn test_basic_experiment_tracking() -> Result<()> {
-    let tracker = ExperimentTracker::new(Config::default()).await?;
-    let mut experiment = tracker.create_experiment("test_experiment")?;
-    let mut run = experiment.start_run()?;
+    let ctx = TestContext::new().await?;
+    let mut experiment = ctx.tracker.create_experiment("test_experiment").await?;
+    let mut run = ctx.tracker.start_run(&mut experiment).await?;
 
     // Test parameters
-    run.set_param("learning_rate", "0.01");
-    run.set_param("batch_size", "32");
+    run.set_param("learning_rate", "0.01")?;
+    run.set_param("batch_size", "32")?;
 
     // Test metrics
     run.log_metric("loss", 0.5);
     run.log_metric("accuracy", 0.95);
 
-    run.finish(RunStatus::Completed);
+    ctx.tracker
+        .finish_run(&mut run, RunStatus::Completed)
+        .await?;
     Ok(())
 }
 
 #[tokio::test]
 async fn test_multiple_runs() -> Result<()> {
-    let tracker = ExperimentTracker::new(Config::default()).await?;
-    let mut experiment = tracker.create_experiment("multi_run_test")?;
+    let ctx = TestContext::new().await?;
+    let mut experiment = ctx.tracker.create_experiment("multi_run_test").await?;
 
     for i in 0..3 {
-        let mut run = experiment.start_run()?;
-        run.set_param("run_number", i.to_string());
+        let mut run = ctx.tracker.start_run(&mut experiment).await?;
+        run.set_param("run_number", i.to_string())?;
         run.log_metric("value", i as f64);
-        run.finish(RunStatus::Completed);
+        ctx.tracker
+            .finish_run(&mut run, RunStatus::Completed)
+            .await?;
     }
 
+    assert_eq!(ctx.tracker.list_runs(experiment.id).await?.len(), 3);
     Ok(())
 }
 
 #[tokio::test]
 async fn test_cleanup_active_run() -> Result<()> {
-    let tracker = ExperimentTracker::new(Config::default()).await?;
-    let mut experiment = tracker.create_experiment("cleanup_test")?;
+    let ctx = TestContext::new().await?;
+    let mut experiment = ctx.tracker.create_experiment("cleanup_test").await?;
 
     // Try to get and end any active run
-    if let Some(mut active_run) = experiment.get_active_run()? {
-        active_run.finish(RunStatus::Completed);
+    if let Some(mut active_run) = ctx.tracker.get_active_run(&mut experiment).await? {
+        ctx.tracker
+            .finish_run(&mut active_run, RunStatus::Completed)
+            .await?;
     }
 
     // Now try a normal run
-    let mut run = experiment.start_run()?;
-    run.set_param("test", "value");
-    run.finish(RunStatus::Completed);
+    let mut run = ctx.tracker.start_run(&mut experiment).await?;
+    run.set_param("test", "value")?;
+    ctx.tracker
+        .finish_run(&mut run, RunStatus::Completed)
+        .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_active_run_recovered_after_crash() -> Result<()> {
+    let ctx = TestContext::new().await?;
+    let mut experiment = ctx.tracker.create_experiment("crash_test").await?;
+    let run = ctx.tracker.start_run(&mut experiment).await?;
+    ctx.tracker.shutdown().await?;
+
+    // A fresh process only knows the experiment id.
+    let tracker = ExperimentTracker::new(ctx.config.clone()).await?;
+    let mut experiment = tracker.get_experiment(experiment.id).await?.unwrap();
+    let mut recovered = tracker
+        .get_active_run(&mut experiment)
+        .await?
+        .expect("running run should be recovered");
+    assert_eq!(recovered.id, run.id);
+
+    tracker
+        .finish_run(&mut recovered, RunStatus::Interrupted)
+        .await?;
+    assert!(tracker.get_active_run(&mut experiment).await?.is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_experiment_and_runs_survive_reopen() -> Result<()> {
+    let ctx = TestContext::new().await?;
+    let mut experiment = ctx.tracker.create_experiment("persisted").await?;
+    let mut run = ctx.tracker.start_run(&mut experiment).await?;
+    run.set_param("learning_rate", "0.01")?;
+    run.log_metric("loss", 0.25);
+    ctx.tracker.save_run(&mut run).await?;
+    ctx.tracker.shutdown().await?;
+
+    let reopened = ExperimentTracker::new(ctx.config.clone()).await?;
+    let loaded = reopened
+        .get_experiment(experiment.id)
+        .await?
+        .expect("experiment should be persisted");
+    assert_eq!(loaded.name, "persisted");
+    assert!(loaded.active_runs.contains(&run.id));
+
+    let loaded_run = reopened
+        .get_run_with_metrics(run.id)
+        .await?
+        .expect("run should be persisted");
+    assert_eq!(loaded_run.experiment_id, experiment.id);
+    assert_eq!(loaded_run.params["learning_rate"], "0.01");
+    assert_eq!(loaded_run.metrics["loss"][0].value, 0.25);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_migrations_upgrade_existing_database() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let url = format!("sqlite:{}?mode=rwc", dir.path().join("legacy.db").display());
+
+    // A database laid out by hand before migrations were tracked.
+    let legacy = sqlx::SqlitePool::connect(&url).await.unwrap();
+    let experiment = ml_tracker::Experiment::new("legacy");
+    let run = ml_tracker::Run::new(experiment.id);
+    sqlx::raw_sql(
+        "CREATE TABLE experiments (
+            id BLOB PRIMARY KEY, name TEXT NOT NULL, description TEXT, tags TEXT NOT NULL,
+            created_at TIMESTAMP NOT NULL, updated_at TIMESTAMP NOT NULL, active_run BLOB
+        );
+        CREATE TABLE runs (
+            id BLOB PRIMARY KEY, experiment_id BLOB NOT NULL, status TEXT NOT NULL,
+            metrics TEXT NOT NULL, params TEXT NOT NULL, tags TEXT NOT NULL,
+            artifacts TEXT NOT NULL, start_time TIMESTAMP NOT NULL, end_time TIMESTAMP
+        );",
+    )
+    .execute(&legacy)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO experiments (id, name, tags, created_at, updated_at, active_run)
+        VALUES (?, ?, '{}', ?, ?, ?)",
+    )
+    .bind(experiment.id)
+    .bind(&experiment.name)
+    .bind(experiment.created_at)
+    .bind(experiment.updated_at)
+    .bind(run.id)
+    .execute(&legacy)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO runs (id, experiment_id, status, metrics, params, tags, artifacts, start_time)
+        VALUES (?, ?, '\"Running\"', ?, ?, '{}', '[]', ?)",
+    )
+    .bind(run.id)
+    .bind(experiment.id)
+    .bind(r#"{"loss": [["2024-01-01T00:00:00Z", 0.5], ["2024-01-01T00:01:00Z", 0.4]]}"#)
+    .bind(r#"{"lr": "0.01", "batch_size": 32, "augment": true, "layers": [64, 128]}"#)
+    .bind(run.start_time)
+    .execute(&legacy)
+    .await
+    .unwrap();
+    legacy.close().await;
+
+    let database = ml_tracker::Database::new(&url).await?;
+    assert!(database.schema_version().await?.is_some());
+    let loaded = database.get_experiment(experiment.id).await?.unwrap();
+    assert_eq!(loaded.name, "legacy");
+    assert!(loaded.active_runs.contains(&run.id));
+
+    let loaded_run = database.get_run_with_metrics(run.id).await?.unwrap();
+    let loss: Vec<f64> = loaded_run.metrics["loss"].iter().map(|p| p.value).collect();
+    assert_eq!(loss, vec![0.5, 0.4]);
+    assert_eq!(loaded_run.params["lr"], "0.01");
+    assert_eq!(loaded_run.params["batch_size"], 32);
+    assert_eq!(loaded_run.params["augment"], true);
+    assert_eq!(loaded_run.params["layers"], serde_json::json!([64, 128]));
+
+    // Moved points use the timestamp format of points written since.
+    let upgraded = sqlx::SqlitePool::connect(&url).await.unwrap();
+    let timestamps: Vec<String> =
+        sqlx::query_scalar("SELECT timestamp FROM metrics ORDER BY timestamp")
+            .fetch_all(&upgraded)
+            .await
+            .unwrap();
+    assert_eq!(
+        timestamps,
+        vec!["2024-01-01T00:00:00+00:00", "2024-01-01T00:01:00+00:00"]
+    );
+    upgraded.close().await;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_logged_metrics_are_durable() -> Result<()> {
+    let ctx = TestContext::new().await?;
+    let mut experiment = ctx.tracker.create_experiment("durable_metrics").await?;
+    let run = ctx.tracker.start_run(&mut experiment).await?;
+
+    let logger = MetricLogger::new(
+        run.id,
+        ctx.tracker.metric_store(),
+        MetricLoggerConfig::default(),
+    );
+    for i in 0..5 {
+        logger.log("loss", 1.0 / (i + 1) as f64).await?;
+    }
+    logger.flush().await?;
+    ctx.tracker.shutdown().await?;
+
+    let reopened = ExperimentTracker::new(ctx.config.clone()).await?;
+    let store = reopened.metric_store();
+    assert_eq!(store.get_metrics(run.id, "loss").await?.len(), 5);
+    let latest = store.get_latest_metric(run.id, "loss").await?.unwrap();
+    assert_eq!(latest.value, 0.2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_saving_run_appends_each_point_once() -> Result<()> {
+    let ctx = TestContext::new().await?;
+    let mut experiment = ctx.tracker.create_experiment("resaved").await?;
+    let mut run = ctx.tracker.start_run(&mut experiment).await?;
+
+    run.log_metric_at_step("loss", 0.9, 0);
+    ctx.tracker.save_run(&mut run).await?;
+    ctx.tracker.save_run(&mut run).await?;
+
+    // Points sharing the stored point's timestamp, or backfilled before it,
+    // are still new points.
+    let stored = run.metrics["loss"][0].clone();
+    for (value, step, timestamp) in [
+        (0.8, 1, stored.timestamp),
+        (0.7, 2, stored.timestamp - chrono::Duration::hours(1)),
+    ] {
+        run.metrics.get_mut("loss").unwrap().push(MetricPoint {
+            value,
+            step: Some(step),
+            timestamp,
+            ..stored.clone()
+        });
+    }
+    ctx.tracker.save_run(&mut run).await?;
+    ctx.tracker.save_run(&mut run).await?;
+
+    let store = ctx.tracker.metric_store();
+    let steps: Vec<_> = store
+        .get_metrics_by_step(run.id, "loss")
+        .await?
+        .iter()
+        .map(|p| (p.step, p.value))
+        .collect();
+    assert_eq!(steps, vec![(Some(0), 0.9), (Some(1), 0.8), (Some(2), 0.7)]);
+
+    // A run read back with its metrics only appends what is logged after.
+    let mut loaded = ctx.tracker.get_run_with_metrics(run.id).await?.unwrap();
+    loaded.log_metric_at_step("loss", 0.6, 3);
+    ctx.tracker.save_run(&mut loaded).await?;
+    let mut loaded = ctx.tracker.get_run(run.id).await?.unwrap();
+    loaded.log_metric_at_step("loss", 0.5, 4);
+    ctx.tracker.save_run(&mut loaded).await?;
+    assert_eq!(store.get_metrics(run.id, "loss").await?.len(), 5);
+
+    // Points are saved in the run's transaction: a point the store rejects
+    // leaves both the run and its other points unsaved.
+    loaded.add_tag("stage", "fine-tune");
+    loaded.log_metric_at_step("loss", 0.4, 5);
+    loaded.log_metric_at_step("loss", 0.3, u64::MAX);
+    assert!(ctx.tracker.save_run(&mut loaded).await.is_err());
+    let stored_run = ctx.tracker.get_run(run.id).await?.unwrap();
+    assert!(!stored_run.tags.contains_key("stage"));
+    assert_eq!(store.get_metrics(run.id, "loss").await?.len(), 5);
+
+    loaded.metrics.get_mut("loss").unwrap().pop();
+    ctx.tracker.save_run(&mut loaded).await?;
+    let stored_run = ctx.tracker.get_run(run.id).await?.unwrap();
+    assert_eq!(stored_run.tags["stage"], "fine-tune");
+    assert_eq!(store.get_metrics(run.id, "loss").await?.len(), 6);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_metrics_ordered_by_step() -> Result<()> {
+    let ctx = TestContext::new().await?;
+    let mut experiment = ctx.tracker.create_experiment("stepped").await?;
+    let mut run = ctx.tracker.start_run(&mut experiment).await?;
+
+    let mut logger = MetricLogger::new(
+        run.id,
+        ctx.tracker.metric_store(),
+        MetricLoggerConfig::default(),
+    );
+    logger.set_epoch(Some(1));
+    logger.log_at_step("loss", 0.3, 20).await?;
+    logger.log_at_step("loss", 0.5, 10).await?;
+    logger.log("loss", 0.9).await?;
+    logger.flush().await?;
+
+    run.log_metric_at_step("accuracy", 0.8, 2);
+    run.log_metric_at_step("accuracy", 0.7, 1);
+    ctx.tracker.save_run(&mut run).await?;
+
+    let store = ctx.tracker.metric_store();
+    let loss = store.get_metrics_by_step(run.id, "loss").await?;
+    let steps: Vec<_> = loss.iter().map(|p| (p.step, p.epoch)).collect();
+    assert_eq!(steps, vec![(Some(10), Some(1)), (Some(20), Some(1))]);
+
+    let accuracy = store.get_metrics_by_step(run.id, "accuracy").await?;
+    let values: Vec<_> = accuracy.iter().map(|p| p.value).collect();
+    assert_eq!(values, vec![0.7, 0.8]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_run_lifecycle_transitions() -> Result<()> {
+    let ctx = TestContext::new().await?;
+    let mut experiment = ctx.tracker.create_experiment("lifecycle").await?;
+    let mut run = ctx.tracker.start_run(&mut experiment).await?;
+
+    ctx.tracker
+        .transition_run(&mut run, RunStatus::Paused)
+        .await?;
+    ctx.tracker
+        .transition_run(&mut run, RunStatus::Running)
+        .await?;
+    assert!(run.finish(RunStatus::Paused).is_err());
+    ctx.tracker.finish_run(&mut run, RunStatus::Killed).await?;
+    assert!(run.finish(RunStatus::Completed).is_err());
+    assert!(run.transition(RunStatus::Running).is_err());
+
+    let loaded = ctx.tracker.get_run(run.id).await?.unwrap();
+    let statuses: Vec<_> = loaded.transitions.iter().map(|t| t.status).collect();
+    assert_eq!(
+        statuses,
+        vec![
+            RunStatus::Running,
+            RunStatus::Paused,
+            RunStatus::Running,
+            RunStatus::Killed
+        ]
+    );
+    assert!(loaded.end_time.is_some());
+
+    let reloaded = ctx.tracker.get_experiment(experiment.id).await?.unwrap();
+    assert!(reloaded.active_runs.is_empty());
+
+    // The stale in-memory handle is reconciled before the next run starts.
+    ctx.tracker.start_run(&mut experiment).await?;
+
+    let mut scheduled = ml_tracker::Run::scheduled(experiment.id);
+    assert!(scheduled.finish(RunStatus::Completed).is_err());
+    scheduled.transition(RunStatus::Running)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_runs_serialized_before_transitions() -> serde_json::Result<()> {
+    let old = serde_json::json!({
+        "id": uuid::Uuid::new_v4(),
+        "experiment_id": uuid::Uuid::new_v4(),
+        "status": "Completed",
+        "metrics": {},
+        "params": { "lr": "0.01" },
+        "tags": {},
+        "artifacts": [],
+        "start_time": "2024-01-01T00:00:00Z",
+        "end_time": "2024-01-01T01:00:00Z",
+    });
+    let run: ml_tracker::Run = serde_json::from_value(old)?;
+    assert_eq!(run.status, RunStatus::Completed);
+    assert!(run.transitions.is_empty());
+    assert!(run.parent_run_id.is_none());
+    assert_eq!(run.params["lr"], "0.01");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_concurrent_runs_per_experiment() -> Result<()> {
+    let ctx = TestContext::new().await?;
+    let mut sweep = ctx.tracker.create_experiment("sweep").await?;
+
+    let mut trials = Vec::new();
+    for seed in 0..3 {
+        let mut run = ctx.tracker.start_run(&mut sweep).await?;
+        run.set_param("seed", seed.to_string())?;
+        ctx.tracker.save_run(&mut run).await?;
+        trials.push(run);
+    }
+    assert_eq!(ctx.tracker.list_active_runs(sweep.id).await?.len(), 3);
+
+    ctx.tracker
+        .finish_run(&mut trials[1], RunStatus::Completed)
+        .await?;
+    let active: Vec<_> = ctx
+        .tracker
+        .list_active_runs(sweep.id)
+        .await?
+        .into_iter()
+        .map(|run| run.id)
+        .collect();
+    assert_eq!(active.len(), 2);
+    assert!(!active.contains(&trials[1].id));
+
+    let mut exclusive = ctx
+        .tracker
+        .register_experiment(ml_tracker::Experiment::new("exclusive").with_exclusive_runs())
+        .await?;
+    let mut run = ctx.tracker.start_run(&mut exclusive).await?;
+    assert!(ctx.tracker.start_run(&mut exclusive).await.is_err());
+    ctx.tracker
+        .finish_run(&mut run, RunStatus::Completed)
+        .await?;
+    ctx.tracker.start_run(&mut exclusive).await?;
+
+    Ok(())
+}
+
+#[test]
+fn test_experiments_serialized_before_concurrent_runs() -> serde_json::Result<()> {
+    let run_id = uuid::Uuid::new_v4();
+    let old = serde_json::json!({
+        "id": uuid::Uuid::new_v4(),
+        "name": "old",
+        "description": null,
+        "tags": {},
+        "created_at": "2024-01-01T00:00:00Z",
+        "updated_at": "2024-01-01T00:00:00Z",
+        "active_run": run_id,
+    });
+    let experiment: ml_tracker::Experiment = serde_json::from_value(old.clone())?;
+    assert_eq!(experiment.active_runs.len(), 1);
+    assert!(experiment.active_runs.contains(&run_id));
+    assert!(!experiment.exclusive);
+
+    let mut idle = old.clone();
+    idle["active_run"] = serde_json::Value::Null;
+    let experiment: ml_tracker::Experiment = serde_json::from_value(idle)?;
+    assert!(experiment.active_runs.is_empty());
+
+    let mut missing = old;
+    missing.as_object_mut().unwrap().remove("active_run");
+    let experiment: ml_tracker::Experiment = serde_json::from_value(missing)?;
+    assert!(experiment.active_runs.is_empty());
+
+    let json = serde_json::to_value(&experiment)?;
+    let round_tripped: ml_tracker::Experiment = serde_json::from_value(json)?;
+    assert_eq!(round_tripped.active_runs, experiment.active_runs);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exclusive_runs_started_concurrently() -> Result<()> {
+    let ctx = TestContext::new().await?;
+    let experiment = ctx
+        .tracker
+        .register_experiment(ml_tracker::Experiment::new("exclusive").with_exclusive_runs())
+        .await?;
+
+    // Each caller holds its own copy of the experiment, as separate
+    // processes would, so only the database can enforce exclusivity.
+    let database = ctx.tracker.database();
+    let starts: Vec<_> = (0..8)
+        .map(|_| {
+            let database = database.clone();
+            tokio::spawn(async move {
+                let mut run = ml_tracker::Run::new(experiment.id);
+                database.start_run(&mut run).await.map(|()| run.id)
+            })
+        })
+        .collect();
+    let mut started = Vec::new();
+    for start in starts {
+        match start.await.unwrap() {
+            Ok(run_id) => started.push(run_id),
+            Err(e) => assert!(matches!(e, TrackerError::InvalidOperation(_)), "{}", e),
+        }
+    }
+    assert_eq!(started.len(), 1);
+
+    let mut copy = ctx.tracker.get_experiment(experiment.id).await?.unwrap();
+    assert_eq!(
+        copy.active_runs.iter().collect::<Vec<_>>(),
+        vec![&started[0]]
+    );
+    assert!(ctx.tracker.start_run(&mut copy).await.is_err());
+
+    let mut run = ctx.tracker.get_run(started[0]).await?.unwrap();
+    ctx.tracker
+        .finish_run(&mut run, RunStatus::Completed)
+        .await?;
+    let next = ctx.tracker.start_run(&mut copy).await?;
+    assert_eq!(copy.active_runs.iter().collect::<Vec<_>>(), vec![&next.id]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_start_prepared_run() -> Result<()> {
+    let ctx = TestContext::new().await?;
+    let mut experiment = ctx
+        .tracker
+        .register_experiment(ml_tracker::Experiment::new("prepared").with_exclusive_runs())
+        .await?;
+
+    let mut prepared = ml_tracker::Run::new(experiment.id);
+    prepared.set_param("lr", 0.01)?;
+    prepared.add_tag("owner", "me");
+    let run = ctx
+        .tracker
+        .start_prepared_run(&mut experiment, prepared)
+        .await?;
+    let stored = ctx.tracker.get_run(run.id).await?.unwrap();
+    assert_eq!(stored.params["lr"], 0.01);
+    assert_eq!(stored.tags["owner"], "me");
+    assert!(experiment.active_runs.contains(&run.id));
+
+    // A rejected run leaves nothing behind.
+    let mut second = ml_tracker::Run::new(experiment.id);
+    second.set_param("lr", 0.1)?;
+    assert!(ctx
+        .tracker
+        .start_prepared_run(&mut experiment, second)
+        .await
+        .is_err());
+    let other = ml_tracker::Run::new(uuid::Uuid::new_v4());
+    assert!(matches!(
+        ctx.tracker.start_prepared_run(&mut experiment, other).await,
+        Err(TrackerError::InvalidOperation(_))
+    ));
+    assert_eq!(ctx.tracker.list_runs(experiment.id).await?.len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_nested_runs() -> Result<()> {
+    let ctx = TestContext::new().await?;
+    let mut experiment = ctx.tracker.create_experiment("cross_validation").await?;
+    let mut parent = ctx.tracker.start_run(&mut experiment).await?;
+
+    let mut folds = Vec::new();
+    for fold in 0..2 {
+        let mut child = ctx.tracker.start_child_run(&parent).await?;
+        child.set_param("fold", fold.to_string())?;
+        ctx.tracker.save_run(&mut child).await?;
+        folds.push(child);
+    }
+    let grandchild = ctx.tracker.start_child_run(&folds[0]).await?;
+
+    let children = ctx.tracker.list_child_runs(parent.id).await?;
+    assert_eq!(children.len(), 2);
+    assert!(children.iter().all(|c| c.parent_run_id == Some(parent.id)));
+
+    let descendants = ctx.tracker.list_descendant_runs(parent.id).await?;
+    assert_eq!(descendants.len(), 3);
+    assert!(descendants.iter().any(|run| run.id == grandchild.id));
+
+    // Only the top-level run occupies the experiment's active slot.
+    let active = ctx.tracker.list_active_runs(experiment.id).await?;
+    assert_eq!(active.len(), 1);
+    assert_eq!(active[0].id, parent.id);
+
+    ctx.tracker
+        .finish_run(&mut parent, RunStatus::Completed)
+        .await?;
+    assert!(parent.start_child_run().is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_list_many_runs_in_chunks() -> Result<()> {
+    let ctx = TestContext::new().await?;
+    let experiment = ctx.tracker.create_experiment("large_sweep").await?;
+    let database = ctx.tracker.database();
+
+    // More runs than SQLite accepts bound parameters in one statement.
+    for index in 0..1200 {
+        let mut run = ml_tracker::Run::new(experiment.id);
+        run.set_param("index", index)?;
+        run.log_metric_at_step("loss", 1.0 / (index + 1) as f64, 0);
+        run.log_metric_at_step("accuracy", 0.5, 0);
+        database.create_run(&mut run).await?;
+    }
+
+    let mut runs = ctx.tracker.list_runs(experiment.id).await?;
+    assert_eq!(runs.len(), 1200);
+    assert!(runs.iter().all(|run| run.params.contains_key("index")));
+    assert!(runs.iter().all(|run| run.metrics.is_empty()));
+
+    ctx.tracker.load_run_metrics(&mut runs, &["loss"]).await?;
+    for run in &runs {
+        let index = run.params["index"].as_u64().unwrap();
+        assert_eq!(run.metrics.len(), 1);
+        assert_eq!(run.metrics["loss"][0].value, 1.0 / (index + 1) as f64);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_search_runs() -> Result<()> {
+    let ctx = TestContext::new().await?;
+    let mut experiment = ctx.tracker.create_experiment("sweep").await?;
+
+    let mut runs = Vec::new();
+    for (lr, val_loss, team) in [
+        ("0.01", 0.25, "nlp"),
+        ("0.1", 0.2, "vision"),
+        ("0.01", 0.4, "nlp"),
+    ] {
+        let mut run = ctx.tracker.start_run(&mut experiment).await?;
+        run.set_param("lr", lr)?;
+        run.add_tag("team", team);
+        run.log_metric("val_loss", val_loss + 0.5);
+        run.log_metric("val_loss", val_loss);
+        ctx.tracker
+            .finish_run(&mut run, RunStatus::Completed)
+            .await?;
+        runs.push(run);
+    }
+
+    let query = RunQuery::new()
+        .with_filter(
+            r#"metrics.val_loss < 0.3 and params.lr = "0.01" and tags.team = "nlp" and status = Completed"#,
+        )?;
+    let found = ctx.tracker.search_runs(&query).await?;
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].id, runs[0].id);
+
+    let query = RunQuery::new()
+        .with_experiment(experiment.id)
+        .with_filter("params.lr >= 0.05 or not (metrics.val_loss > 0.3)")?
+        .with_order_by("metrics.val_loss desc")?;
+    let found = ctx.tracker.search_runs(&query).await?;
+    let ids: Vec<_> = found.iter().map(|run| run.id).collect();
+    assert_eq!(ids, vec![runs[0].id, runs[1].id]);
+
+    let query = RunQuery::new()
+        .with_order_by("metrics.val_loss")?
+        .with_limit(1)
+        .with_offset(1);
+    let mut found = ctx.tracker.search_runs(&query).await?;
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].id, runs[0].id);
+    assert!(found[0].metrics.is_empty());
+    ctx.tracker
+        .load_run_metrics(&mut found, &["val_loss"])
+        .await?;
+    assert_eq!(found[0].metrics["val_loss"].len(), 2);
+
+    assert!(RunQuery::new().with_filter("metrics.loss < ").is_err());
+    assert!(RunQuery::new().with_filter("status = Done").is_ok());
+    let invalid = RunQuery::new().with_filter("status = Done")?;
+    assert!(ctx.tracker.search_runs(&invalid).await.is_err());
+
+    // A quoted number matches params logged as numbers as well as text.
+    let mut typed = ctx.tracker.create_experiment("typed").await?;
+    let mut ids = Vec::new();
+    for lr in [
+        serde_json::json!(0.01),
+        serde_json::json!("0.01"),
+        serde_json::json!(0.1),
+    ] {
+        let mut run = ctx.tracker.start_run(&mut typed).await?;
+        run.set_param("lr", lr)?;
+        ctx.tracker.save_run(&mut run).await?;
+        ids.push(run.id);
+    }
+    let search = |filter: &str| {
+        RunQuery::new()
+            .with_experiment(typed.id)
+            .with_filter(filter)
+    };
+    let found = ctx
+        .tracker
+        .search_runs(&search(r#"params.lr = "0.01""#)?)
+        .await?;
+    let found: HashSet<_> = found.iter().map(|run| run.id).collect();
+    assert_eq!(found, ids[..2].iter().copied().collect());
+    let found = ctx
+        .tracker
+        .search_runs(&search(r#"params.lr != "0.01""#)?)
+        .await?;
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].id, ids[2]);
+    let found = ctx
+        .tracker
+        .search_runs(&search(r#"params.lr > "0.05""#)?)
+        .await?;
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].id, ids[2]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_commands_use_configured_database() -> Result<()> {
+    let ctx = TestContext::new().await?;
+    let app = CliApp::new().with_config(ctx.config.clone());
+    let cli = |args: &[&str]| {
+        Cli::try_parse_from(std::iter::once("ml-tracker").chain(args.iter().copied()))
+            .expect("valid command line")
+    };
+
+    app.execute(cli(&["create-experiment", "--name", "from_cli"]))
+        .await?;
+    let experiments = ctx.tracker.list_experiments().await?;
+    assert_eq!(experiments.len(), 1);
+    assert_eq!(experiments[0].name, "from_cli");
+
+    let mut experiment = experiments.into_iter().next().unwrap();
+    let mut run = ctx.tracker.start_run(&mut experiment).await?;
+    run.set_param("lr", "0.01")?;
+    run.log_metric("loss", 0.5);
+    ctx.tracker.save_run(&mut run).await?;
+    LocalStorage::new(&ctx.config.storage_path)
+        .store_artifact(run.id, "model.bin", b"weights")
+        .await?;
+
+    let run_id = run.id.to_string();
+    app.execute(cli(&["list-experiments"])).await?;
+    app.execute(cli(&["show-run", "--run-id", &run_id])).await?;
+    app.execute(cli(&["list-artifacts", "--run-id", &run_id]))
+        .await?;
+
+    let missing = uuid::Uuid::new_v4().to_string();
+    assert!(matches!(
+        app.execute(cli(&["show-run", "--run-id", &missing])).await,
+        Err(TrackerError::NotFound(_))
+    ));
+    app.execute(cli(&["list-artifacts", "--run-id", &missing]))
+        .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_storage_backend_selection() -> Result<()> {
+    let ctx = TestContext::new().await?;
+    let run_id = uuid::Uuid::new_v4();
+
+    // Without a storage URI, artifacts land under `storage_path`.
+    let artifact = ctx
+        .tracker
+        .artifacts()
+        .store(run_id, "weights.bin", b"local")
+        .await?;
+    assert!(std::path::Path::new(&artifact.path).starts_with(&ctx.config.storage_path));
+    assert_eq!(ctx.tracker.artifacts().get(&artifact).await?, b"local");
+
+    let config = ctx.config.clone().with_storage_uri("memory://");
+    let tracker = ExperimentTracker::new(config).await?;
+    let path = tracker
+        .storage()
+        .lock()
+        .await
+        .store_artifact(run_id, "model.pt", b"in memory")
+        .await?;
+    assert_eq!(tracker.artifacts().list(run_id).await?, vec![path.clone()]);
+    assert_eq!(
+        tracker.storage().lock().await.get_artifact(&path).await?,
+        b"in memory"
+    );
+
+    assert_eq!(
+        "file:///var/lib/mltracker".parse::<StorageBackend>()?,
+        StorageBackend::Local("/var/lib/mltracker".into())
+    );
+    assert_eq!(
+        "s3://models/team/nlp/".parse::<StorageBackend>()?,
+        StorageBackend::S3 {
+            bucket: "models".to_string(),
+            prefix: Some("team/nlp".to_string()),
+        }
+    );
+    // Opening only builds the client; nothing is sent until an artifact is
+    // stored.
+    #[cfg(feature = "s3")]
+    "s3://models/team/nlp"
+        .parse::<StorageBackend>()?
+        .open()
+        .await?;
+    #[cfg(not(feature = "s3"))]
+    assert!(matches!(
+        "s3://models".parse::<StorageBackend>()?.open().await,
+        Err(TrackerError::Storage(_))
+    ));
+
+    for invalid in ["ftp://host/path", "s3://", "/no/scheme", "memory://x"] {
+        assert!(matches!(
+            invalid.parse::<StorageBackend>(),
+            Err(TrackerError::InvalidConfig(_))
+        ));
+    }
+
+    // Backends that predate `artifact_size` get a size from the content.
+    let storage = MinimalStorage(InMemoryStorage::new());
+    let path = storage
+        .store_artifact(run_id, "notes.txt", b"twelve bytes")
+        .await?;
+    assert_eq!(storage.artifact_size(&path).await?, 12);
+
+    Ok(())
+}
+
+/// A backend implementing only the required `Storage` methods.
+struct MinimalStorage(InMemoryStorage);
+
+#[async_trait::async_trait]
+impl Storage for MinimalStorage {
+    async fn store_artifact(&self, run_id: uuid::Uuid, name: &str, data: &[u8]) -> Result<String> {
+        self.0.store_artifact(run_id, name, data).await
+    }
+
+    async fn get_artifact(&self, path: &str) -> Result<Vec<u8>> {
+        self.0.get_artifact(path).await
+    }
+
+    async fn list_artifacts(&self, run_id: uuid::Uuid) -> Result<Vec<String>> {
+        self.0.list_artifacts(run_id).await
+    }
+
+    async fn delete_artifact(&self, path: &str) -> Result<()> {
+        self.0.delete_artifact(path).await
+    }
+}
+
+#[tokio::test]
+async fn test_active_run_writes_through() -> Result<()> {
+    let ctx = TestContext::new().await?;
+    let mut experiment = ctx.tracker.create_experiment("handles").await?;
+    let run = ctx.tracker.start_active_run(&mut experiment).await?;
+
+    run.log_params([("lr", "0.01"), ("batch_size", "32")])
+        .await?;
+    run.set_tag("team", "nlp").await?;
+
+    // Clones share the run, e.g. with a spawned evaluation task.
+    let eval = run.clone();
+    tokio::spawn(async move { eval.log_metric_at_step("val_loss", 0.4, 1).await })
+        .await
+        .unwrap()?;
+    run.log_metric("loss", 2.5).await?;
+    run.log_metric("loss", 1.5).await?;
+
+    let artifact = run.log_artifact("model", "model.pt", b"weights").await?;
+    assert_eq!(artifact.name, "model");
+    assert_eq!(ctx.tracker.artifacts().get(&artifact).await?, b"weights");
+
+    let stored = ctx.tracker.get_run_with_metrics(run.id()).await?.unwrap();
+    assert_eq!(stored.params["batch_size"], "32");
+    assert_eq!(stored.tags["team"], "nlp");
+    assert_eq!(stored.artifacts, vec![artifact.path.clone()]);
+    assert_eq!(stored.metrics["loss"].len(), 2);
+    assert_eq!(stored.metrics["val_loss"][0].step, Some(1));
+
+    run.finish().await?;
+    assert_eq!(run.status().await, RunStatus::Completed);
+    assert!(run.log_metric("loss", 1.0).await.is_err());
+    assert!(run.set_tag("late", "yes").await.is_err());
+
+    let snapshot = run.snapshot().await?;
+    assert_eq!(snapshot.status, RunStatus::Completed);
+    assert_eq!(snapshot.metrics["loss"].len(), 2);
+    assert!(ctx
+        .tracker
+        .list_active_runs(experiment.id)
+        .await?
+        .is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_guarded_runs_record_failures() -> Result<()> {
+    let ctx = TestContext::new().await?;
+    let mut experiment = ctx.tracker.create_experiment("guarded").await?;
+
+    // Buffered metrics are flushed when the work succeeds.
+    let run = ctx.tracker.start_active_run(&mut experiment).await?;
+    let steps = run
+        .run_guarded(|run| async move {
+            let logger = run.logger();
+            for step in 0..3 {
+                logger.log_at_step("loss", 1.0, step).await?;
+            }
+            Ok(3)
+        })
+        .await?;
+    assert_eq!(steps, 3);
+    let stored = ctx.tracker.get_run_with_metrics(run.id()).await?.unwrap();
+    assert_eq!(stored.status, RunStatus::Completed);
+    assert_eq!(stored.metrics["loss"].len(), 3);
+
+    // Errors are recorded and returned.
+    let run = ctx.tracker.start_active_run(&mut experiment).await?;
+    let result: Result<()> = run
+        .run_guarded(|_| async { Err(TrackerError::InvalidOperation("diverged".to_string())) })
+        .await;
+    assert!(result.is_err());
+    let stored = ctx.tracker.get_run(run.id()).await?.unwrap();
+    assert_eq!(stored.status, RunStatus::Failed);
+    assert!(stored.tags["error"].contains("diverged"));
+
+    // Panics are recorded before the panic resumes.
+    let run = ctx.tracker.start_active_run(&mut experiment).await?;
+    let guarded = run.clone();
+    let joined = tokio::spawn(async move {
+        let result: Result<()> = guarded
+            .run_guarded(|_| async { panic!("exploding gradients") })
+            .await;
+        result
+    })
+    .await;
+    assert!(joined.unwrap_err().is_panic());
+    let stored = ctx.tracker.get_run(run.id()).await?.unwrap();
+    assert_eq!(stored.status, RunStatus::Failed);
+    assert_eq!(stored.tags["panic"], "exploding gradients");
+
+    // Dropping every handle of an unfinished run flushes its logger and
+    // fails it before the drop returns.
+    let run = ctx.tracker.start_active_run(&mut experiment).await?;
+    let run_id = run.id();
+    run.logger().log("loss", 0.5).await?;
+    drop(run);
+    let stored = ctx.tracker.get_run_with_metrics(run_id).await?.unwrap();
+    assert_eq!(stored.status, RunStatus::Failed);
+    assert!(stored.tags["error"].contains("dropped"));
+    assert_eq!(stored.metrics["loss"].len(), 1);
+
+    // A panic unwinding through the last handle is recorded with its message.
+    let run = ctx.tracker.start_active_run(&mut experiment).await?;
+    let run_id = run.id();
+    let joined = tokio::spawn(async move {
+        let _run = run;
+        panic!("loss is NaN at step {}", 7);
+    })
+    .await;
+    assert!(joined.unwrap_err().is_panic());
+    let stored = ctx.tracker.get_run(run_id).await?.unwrap();
+    assert_eq!(stored.status, RunStatus::Failed);
+    assert_eq!(stored.tags["panic"], "loss is NaN at step 7");
+    assert!(ctx
+        .tracker
+        .list_active_runs(experiment.id)
+        .await?
+        .is_empty());
+
+    // A signal aborts the work and interrupts the run. This is the only
+    // guarded run in the test binary, so no other test sees the signal.
+    #[cfg(unix)]
+    {
+        let run = ctx.tracker.start_active_run(&mut experiment).await?;
+        let guarded = run.clone();
+        let task = tokio::spawn(async move {
+            let result: Result<()> = guarded
+                .run_guarded(|_| async {
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                    Ok(())
+                })
+                .await;
+            result
+        });
+        // Give `run_guarded` time to install its signal handlers.
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let killed = std::process::Command::new("kill")
+            .args(["-INT", &std::process::id().to_string()])
+            .status()?;
+        assert!(killed.success());
+
+        let result = tokio::time::timeout(Duration::from_secs(10), task)
+            .await
+            .expect("signal interrupts the guarded run")
+            .unwrap();
+        assert!(matches!(result, Err(TrackerError::Interrupted(_))));
+        let stored = ctx.tracker.get_run(run.id()).await?.unwrap();
+        assert_eq!(stored.status, RunStatus::Interrupted);
+        assert_eq!(stored.tags["signal"], "SIGINT");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_dropped_run_fails_during_runtime_shutdown() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let config = Config {
+        storage_path: dir.path().join("artifacts"),
+        database_url: format!("sqlite:{}", dir.path().join("experiments.db").display()),
+        ..Config::default()
+    };
+    let new_runtime = || {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+    };
+
+    // One run is held by a task that never finishes, so it is only dropped
+    // when the runtime shuts down; the other is dropped as `main` would
+    // return an error.
+    let runtime = new_runtime();
+    let (held, returned) = runtime.block_on(async {
+        let tracker = ExperimentTracker::new(config.clone()).await?;
+        let mut experiment = tracker.create_experiment("shutdown").await?;
+
+        let held = tracker.start_active_run(&mut experiment).await?;
+        let held_id = held.id();
+        held.log_metric("loss", 1.0).await?;
+        held.logger().log("accuracy", 0.5).await?;
+        tokio::spawn(async move {
+            let _run = held;
+            std::future::pending::<()>().await;
+        });
+
+        let returned = tracker.start_active_run(&mut experiment).await?;
+        returned.log_metric("loss", 1.0).await?;
+        returned.logger().log("accuracy", 0.5).await?;
+        Ok::<_, TrackerError>((held_id, returned.id()))
+    })?;
+    drop(runtime);
+
+    new_runtime().block_on(async {
+        let tracker = ExperimentTracker::new(config).await?;
+        for run_id in [held, returned] {
+            let run = tracker.get_run_with_metrics(run_id).await?.unwrap();
+            assert_eq!(run.status, RunStatus::Failed, "run {}", run_id);
+            assert_eq!(run.metrics["loss"].len(), 1);
+            assert_eq!(run.metrics["accuracy"].len(), 1);
+        }
+        Ok(())
+    })
+}
+
+/// Wraps `InMemoryMetricStore` with a fixed delay on every write.
+struct SlowStore {
+    inner: InMemoryMetricStore,
+    delay: Duration,
+}
+
+#[async_trait::async_trait]
+impl MetricStore for SlowStore {
+    fn new() -> Result<Self> {
+        Ok(Self {
+            inner: InMemoryMetricStore::new()?,
+            delay: Duration::from_millis(50),
+        })
+    }
+
+    async fn store_metrics(&self, metrics: &[MetricPoint]) -> Result<()> {
+        tokio::time::sleep(self.delay).await;
+        self.inner.store_metrics(metrics).await
+    }
+
+    async fn get_metrics(&self, run_id: uuid::Uuid, metric_name: &str) -> Result<Vec<MetricPoint>> {
+        self.inner.get_metrics(run_id, metric_name).await
+    }
+
+    async fn get_latest_metric(
+        &self,
+        run_id: uuid::Uuid,
+        metric_name: &str,
+    ) -> Result<Option<MetricPoint>> {
+        self.inner.get_latest_metric(run_id, metric_name).await
+    }
+
+    async fn list_metric_keys(&self, run_id: uuid::Uuid) -> Result<Vec<String>> {
+        self.inner.list_metric_keys(run_id).await
+    }
+}
+
+#[tokio::test]
+async fn test_metric_logger_background_worker() -> Result<()> {
+    let store: Arc<dyn MetricStore> = Arc::new(InMemoryMetricStore::new()?);
+    let run_id = uuid::Uuid::new_v4();
+    let logger = MetricLogger::new(
+        run_id,
+        store.clone(),
+        MetricLoggerConfig {
+            buffer_size: 1000,
+            flush_interval: Duration::from_millis(20),
+            ..MetricLoggerConfig::default()
+        },
+    );
+
+    // Clones log concurrently into the same buffer.
+    let tasks: Vec<_> = (0..4)
+        .map(|worker| {
+            let logger = logger.clone();
+            tokio::spawn(async move {
+                for step in 0..25 {
+                    logger
+                        .log_at_step(format!("loss_{worker}"), 1.0, step)
+                        .await?;
+                }
+                Result::Ok(())
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap()?;
+    }
+
+    // The interval flush writes the points without an explicit flush.
+    let mut flushed = 0;
+    for _ in 0..100 {
+        flushed = store.get_metrics(run_id, "loss_3").await?.len();
+        if flushed == 25 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(flushed, 25);
+
+    logger.log("final", 1.0).await?;
+    logger.shutdown().await?;
+    assert_eq!(store.get_metrics(run_id, "final").await?.len(), 1);
+    assert!(logger.log("late", 1.0).await.is_err());
+    assert!(logger.clone().flush().await.is_err());
+
+    // With a one-slot queue and a slow store, logging waits on the store.
+    let slow: Arc<dyn MetricStore> = Arc::new(SlowStore::new()?);
+    let logger = MetricLogger::new(
+        run_id,
+        slow.clone(),
+        MetricLoggerConfig {
+            buffer_size: 1,
+            queue_capacity: 1,
+            ..MetricLoggerConfig::default()
+        },
+    );
+    let started = std::time::Instant::now();
+    for i in 0..5 {
+        logger.log("slow", i as f64).await?;
+    }
+    assert!(started.elapsed() >= Duration::from_millis(100));
+    logger.shutdown().await?;
+    assert_eq!(slow.get_metrics(run_id, "slow").await?.len(), 5);
+
+    // A zero flush interval turns the timer off instead of stopping the
+    // worker.
+    let untimed = MetricLogger::new(
+        run_id,
+        slow.clone(),
+        MetricLoggerConfig {
+            flush_interval: Duration::ZERO,
+            ..MetricLoggerConfig::default()
+        },
+    );
+    untimed.log("untimed", 1.0).await?;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(slow.get_metrics(run_id, "untimed").await?.is_empty());
+    untimed.flush().await?;
+    untimed.log("untimed", 2.0).await?;
+    untimed.shutdown().await?;
+    assert_eq!(slow.get_metrics(run_id, "untimed").await?.len(), 2);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_writers_to_in_memory_store() -> Result<()> {
+    let store: Arc<dyn MetricStore> = Arc::new(InMemoryMetricStore::new()?);
+    let run_ids: Vec<_> = (0..4).map(|_| uuid::Uuid::new_v4()).collect();
+
+    let tasks: Vec<_> = (0..16)
+        .map(|worker| {
+            let logger = MetricLogger::new(
+                run_ids[worker % run_ids.len()],
+                store.clone(),
+                MetricLoggerConfig {
+                    buffer_size: 64,
+                    ..MetricLoggerConfig::default()
+                },
+            );
+            tokio::spawn(async move {
+                for step in 0..500 {
+                    logger.log_at_step("shared", step as f64, step).await?;
+                    logger.log(format!("own_{worker}"), step as f64).await?;
+                }
+                logger.shutdown().await
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap()?;
+    }
+
+    for run_id in &run_ids {
+        assert_eq!(store.get_metrics(*run_id, "shared").await?.len(), 4 * 500);
+    }
+    for worker in 0..16 {
+        let run_id = run_ids[worker % run_ids.len()];
+        let own = store.get_metrics(run_id, &format!("own_{worker}")).await?;
+        assert_eq!(own.len(), 500);
+        assert!(own.windows(2).all(|pair| pair[0].value < pair[1].value));
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_metric_aggregates_and_downsampling() -> Result<()> {
+    let ctx = TestContext::new().await?;
+    let run_id = uuid::Uuid::new_v4();
+    let start = chrono::Utc::now();
+    let points: Vec<MetricPoint> = (0..1000u64)
+        .map(|i| MetricPoint {
+            run_id,
+            name: "loss".to_string(),
+            value: if i == 500 { 50.0 } else { 1.0 / (i + 1) as f64 },
+            timestamp: start + chrono::Duration::milliseconds(i as i64),
+            step: (i % 10 != 9).then_some(i),
+            epoch: Some(i / 100),
+        })
+        .collect();
+
+    let stores: Vec<Arc<dyn MetricStore>> = vec![
+        Arc::new(InMemoryMetricStore::new()?),
+        ctx.tracker.metric_store(),
+    ];
+    for store in &stores {
+        store.store_metrics(&points).await?;
+
+        let window = store
+            .get_metrics_in_range(run_id, "loss", MetricRange::steps(100..110))
+            .await?;
+        let steps: Vec<_> = window.iter().filter_map(|p| p.step).collect();
+        assert_eq!(steps, (100..109).collect::<Vec<_>>());
+
+        let summary = store
+            .summarize_metric(run_id, "loss", MetricRange::steps(..=500))
+            .await?
+            .unwrap();
+        assert_eq!(summary.count, 451);
+        assert_eq!(summary.max, 50.0);
+        assert_eq!(summary.last, 50.0);
+        assert_eq!(summary.min, 1.0 / 499.0);
+
+        let all = store
+            .summarize_metric(run_id, "loss", MetricRange::all())
+            .await?
+            .unwrap();
+        assert_eq!(all.count, 1000);
+        assert_eq!(all.last, 1.0 / 1000.0);
+        let mean = points.iter().map(|p| p.value).sum::<f64>() / 1000.0;
+        assert!((all.mean - mean).abs() < 1e-9);
+
+        let late = store
+            .summarize_metric(
+                run_id,
+                "loss",
+                MetricRange::time(start + chrono::Duration::milliseconds(990)..),
+            )
+            .await?
+            .unwrap();
+        assert_eq!(late.count, 10);
+        assert!(store
+            .summarize_metric(run_id, "missing", MetricRange::all())
+            .await?
+            .is_none());
+
+        // LTTB keeps the endpoints and the spike.
+        let sampled = store
+            .downsample_metric(run_id, "loss", MetricRange::all(), 50, Downsample::Lttb)
+            .await?;
+        assert_eq!(sampled.len(), 50);
+        assert_eq!(sampled.first().unwrap().step, Some(0));
+        assert_eq!(sampled.last().unwrap().value, 1.0 / 1000.0);
+        assert!(sampled.iter().any(|p| p.value == 50.0));
+
+        let buckets = store
+            .downsample_metric(
+                run_id,
+                "loss",
+                MetricRange::steps(..),
+                10,
+                Downsample::BucketMean,
+            )
+            .await?;
+        assert_eq!(buckets.len(), 10);
+        let firsts: Vec<_> = buckets.iter().map(|p| p.step.unwrap()).collect();
+        assert_eq!(firsts, (0..10).map(|b| b * 100).collect::<Vec<_>>());
+        let expected = points[..100]
+            .iter()
+            .filter(|p| p.step.is_some())
+            .map(|p| p.value)
+            .sum::<f64>()
+            / 90.0;
+        assert!((buckets[0].value - expected).abs() < 1e-9);
+
+        let by_time = store
+            .downsample_metric(
+                run_id,
+                "loss",
+                MetricRange::all(),
+                4,
+                Downsample::BucketMean,
+            )
+            .await?;
+        assert_eq!(by_time.len(), 4);
+        assert_eq!(by_time[0].timestamp, start);
+
+        let short = store
+            .downsample_metric(
+                run_id,
+                "loss",
+                MetricRange::steps(..5),
+                10,
+                Downsample::Lttb,
+            )
+            .await?;
+        assert_eq!(short.len(), 5);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bucket_means_with_extreme_steps() -> Result<()> {
+    let ctx = TestContext::new().await?;
+    let stores: Vec<(Arc<dyn MetricStore>, u64)> = vec![
+        (Arc::new(InMemoryMetricStore::new()?), u64::MAX),
+        // SQLite integers are signed; larger steps are rejected below.
+        (ctx.tracker.metric_store(), i64::MAX as u64),
+    ];
+    for (store, max_step) in &stores {
+        let run_id = uuid::Uuid::new_v4();
+        let points: Vec<MetricPoint> = [0, 1, max_step - 1, *max_step]
+            .into_iter()
+            .enumerate()
+            .map(|(i, step)| MetricPoint {
+                run_id,
+                name: "loss".to_string(),
+                value: i as f64,
+                timestamp: chrono::Utc::now(),
+                step: Some(step),
+                epoch: None,
+            })
+            .collect();
+        store.store_metrics(&points).await?;
+
+        let buckets = store
+            .downsample_metric(
+                run_id,
+                "loss",
+                MetricRange::steps(..),
+                2,
+                Downsample::BucketMean,
+            )
+            .await?;
+        let means: Vec<_> = buckets.iter().map(|p| (p.step, p.value)).collect();
+        assert_eq!(means, vec![(Some(0), 0.5), (Some(max_step - 1), 2.5)]);
+
+        let tail = store
+            .get_metrics_in_range(run_id, "loss", MetricRange::steps(1..=u64::MAX))
+            .await?;
+        assert_eq!(tail.len(), 3);
+    }
+
+    // Steps SQLite cannot hold fail instead of wrapping to negative numbers,
+    // and nothing from the batch is stored.
+    let store = ctx.tracker.metric_store();
+    let run_id = uuid::Uuid::new_v4();
+    let point = |step| MetricPoint {
+        run_id,
+        name: "loss".to_string(),
+        value: 1.0,
+        timestamp: chrono::Utc::now(),
+        step: Some(step),
+        epoch: None,
+    };
+    assert!(matches!(
+        store
+            .store_metrics(&[point(0), point(i64::MAX as u64 + 1)])
+            .await,
+        Err(TrackerError::InvalidOperation(_))
+    ));
+    assert!(store.get_metrics(run_id, "loss").await?.is_empty());
+    store.store_metrics(&[point(5)]).await?;
+    assert!(store
+        .get_metrics_in_range(run_id, "loss", MetricRange::steps(u64::MAX..))
+        .await?
+        .is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_list_metric_keys_and_fetch_many() -> Result<()> {
+    let ctx = TestContext::new().await?;
+    let runs = [uuid::Uuid::new_v4(), uuid::Uuid::new_v4()];
+    let idle = uuid::Uuid::new_v4();
+    let point = |run_id, name: &str, value| MetricPoint {
+        run_id,
+        name: name.to_string(),
+        value,
+        timestamp: chrono::Utc::now(),
+        step: None,
+        epoch: None,
+    };
+    let points = vec![
+        point(runs[0], "loss", 0.9),
+        point(runs[0], "accuracy", 0.1),
+        point(runs[1], "loss", 0.8),
+        point(runs[0], "loss", 0.5),
+        point(runs[1], "lr", 0.01),
+    ];
+
+    let stores: Vec<Arc<dyn MetricStore>> = vec![
+        Arc::new(InMemoryMetricStore::new()?),
+        ctx.tracker.metric_store(),
+    ];
+    for store in &stores {
+        store.store_metrics(&points).await?;
+
+        assert_eq!(store.list_metric_keys(runs[0]).await?, ["accuracy", "loss"]);
+        assert_eq!(store.list_metric_keys(runs[1]).await?, ["loss", "lr"]);
+        assert!(store.list_metric_keys(idle).await?.is_empty());
+
+        let all = store
+            .get_metrics_many(&[runs[0], runs[1], idle], &[])
+            .await?;
+        assert_eq!(all.len(), 3);
+        assert!(all[&idle].is_empty());
+        let loss: Vec<_> = all[&runs[0]]["loss"].iter().map(|p| p.value).collect();
+        assert_eq!(loss, vec![0.9, 0.5]);
+        assert_eq!(all[&runs[1]].len(), 2);
+
+        let some = store.get_metrics_many(&runs, &["loss", "missing"]).await?;
+        assert_eq!(some[&runs[0]].keys().collect::<Vec<_>>(), ["loss"]);
+        assert_eq!(some[&runs[1]]["loss"][0].value, 0.8);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_typed_and_nested_params() -> Result<()> {
+    let ctx = TestContext::new().await?;
+    let mut experiment = ctx.tracker.create_experiment("typed_params").await?;
+
+    let mut runs = Vec::new();
+    for (lr, augment) in [(1e-3, true), (1e-5, false), (5e-4, true)] {
+        let mut run = ctx.tracker.start_run(&mut experiment).await?;
+        run.set_param("lr", lr)?;
+        run.set_param("augment", augment)?;
+        run.set_param(
+            "model",
+            serde_json::json!({ "layers": [64, 128], "head": { "dropout": lr * 100.0 } }),
+        )?;
+        ctx.tracker
+            .finish_run(&mut run, RunStatus::Completed)
+            .await?;
+        runs.push(run);
+    }
+
+    let loaded = ctx.tracker.get_run(runs[0].id).await?.unwrap();
+    assert_eq!(loaded.params["lr"], 1e-3);
+    assert_eq!(loaded.params["augment"], true);
+    assert_eq!(loaded.params["model.layers"], serde_json::json!([64, 128]));
+    assert_eq!(loaded.params["model.head.dropout"], 0.1);
+    assert!(!loaded.params.contains_key("model"));
+
+    let search = |filter: &str| {
+        let query = RunQuery::new()
+            .with_experiment(experiment.id)
+            .with_filter(filter)
+            .map(|query| query.with_order_by("params.lr").unwrap());
+        async {
+            let found = ctx.tracker.search_runs(&query?).await?;
+            Ok::<_, TrackerError>(found.iter().map(|run| run.id).collect::<Vec<_>>())
+        }
+    };
+    assert_eq!(
+        search("params.lr > 1e-4").await?,
+        vec![runs[2].id, runs[0].id]
+    );
+    assert_eq!(search("params.augment = false").await?, vec![runs[1].id]);
+    assert_eq!(
+        search("params.augment != false and params.model.head.dropout >= 0.05").await?,
+        vec![runs[2].id, runs[0].id]
+    );
+    assert!(search("params.augment > true").await.is_err());
+    assert!(search("metrics.loss = true").await.is_err());
+
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct TrainConfig {
+    lr: f64,
+    batch_size: u32,
+    augment: bool,
+    layers: Vec<u32>,
+    optimizer: OptimizerConfig,
+    scheduler: Option<String>,
+}
+
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct OptimizerConfig {
+    name: String,
+    momentum: f64,
+}
+
+#[tokio::test]
+async fn test_params_from_and_into_config_structs() -> Result<()> {
+    let ctx = TestContext::new().await?;
+    let mut experiment = ctx.tracker.create_experiment("configs").await?;
+    let config = TrainConfig {
+        lr: 3e-4,
+        batch_size: 64,
+        augment: true,
+        layers: vec![256, 128],
+        optimizer: OptimizerConfig {
+            name: "sgd".to_string(),
+            momentum: 0.9,
+        },
+        scheduler: None,
+    };
+
+    let run = ctx.tracker.start_active_run(&mut experiment).await?;
+    run.log_params_from(&config).await?;
+    assert!(run.log_params_from(&42).await.is_err());
+    run.finish().await?;
+
+    let stored = ctx.tracker.get_run(run.id()).await?.unwrap();
+    assert_eq!(stored.params["optimizer.name"], "sgd");
+    assert_eq!(stored.params["batch_size"], 64);
+    assert_eq!(stored.params_into::<TrainConfig>()?, config);
+    assert_eq!(run.params_into::<TrainConfig>().await?, config);
+
+    let mut clashing = stored.clone();
+    clashing.set_param("lr.warmup", 100)?;
+    assert!(clashing.params_into::<serde_json::Value>().is_err());
+
+    // Map keys may not contain the nesting separator, so maps round-trip.
+    let weights = serde_json::json!({ "weights": { "encoder": 0.5, "decoder": 0.25 } });
+    let mut mapped = ml_tracker::Run::new(experiment.id);
+    mapped.log_params_from(&weights)?;
+    assert_eq!(mapped.params_into::<serde_json::Value>()?, weights);
+    let dotted = serde_json::json!({ "weights": { "layer.0": 0.5 } });
+    assert!(matches!(
+        mapped.log_params_from(&dotted),
+        Err(TrackerError::InvalidOperation(_))
+    ));
+    assert!(mapped
+        .set_param("schedule", serde_json::json!({ "1.5": "warmup" }))
+        .is_err());
+    assert_eq!(mapped.params.len(), 2);
+
+    // A config without fields, or an empty key, records nothing.
+    #[derive(serde::Serialize)]
+    struct NoFields {}
+    mapped.log_params_from(&NoFields {})?;
+    mapped.log_params_from(&serde_json::json!({}))?;
+    assert!(mapped.set_param("", 1).is_err());
+    assert!(mapped
+        .set_param("schedule", serde_json::json!({ "": 1 }))
+        .is_err());
+    assert_eq!(mapped.params.len(), 2);
+    assert!(!mapped.params.contains_key(""));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_params_are_write_once() -> Result<()> {
+    let ctx = TestContext::new().await?;
+    let mut experiment = ctx.tracker.create_experiment("write_once").await?;
+    let mut run = ctx.tracker.start_run(&mut experiment).await?;
+
+    run.set_param("lr", 0.01)?;
+    run.set_param("lr", 0.01)?;
+    assert!(matches!(
+        run.set_param("lr", 0.1),
+        Err(TrackerError::ParamConflict(_))
+    ));
+    // A conflicting nested key rejects the whole map.
+    assert!(run
+        .set_param("opt", serde_json::json!({ "name": "sgd" }))
+        .is_ok());
+    assert!(run
+        .set_param("opt", serde_json::json!({ "name": "adam", "beta": 0.9 }))
+        .is_err());
+    assert!(!run.params.contains_key("opt.beta"));
+    run.add_tag("stage", "warmup");
+    run.add_tag("stage", "main");
+    ctx.tracker.save_run(&mut run).await?;
+
+    // A stale copy of the run cannot change a stored param either.
+    let mut stale = run.clone();
+    stale
+        .params
+        .insert("lr".to_string(), serde_json::json!(0.5));
+    stale.set_param("seed", 7)?;
+    assert!(matches!(
+        ctx.tracker.save_run(&mut stale).await,
+        Err(TrackerError::ParamConflict(_))
+    ));
+    let stored = ctx.tracker.get_run(run.id).await?.unwrap();
+    assert_eq!(stored.params["lr"], 0.01);
+    assert!(!stored.params.contains_key("seed"));
+    assert_eq!(stored.tags["stage"], "main");
+
+    let active = ctx.tracker.active_run(stored);
+    active.log_param("batch_size", 32).await?;
+    assert!(active.log_param("lr", 0.2).await.is_err());
+    active.log_param("lr", 0.01).await?;
+    active.set_tag("stage", "done").await?;
+    active.finish().await?;
+
+    let query = RunQuery::new().with_filter("params.lr = 0.01 and params.batch_size >= 32")?;
+    assert_eq!(ctx.tracker.search_runs(&query).await?.len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_concurrent_writers_of_the_same_param() -> Result<()> {
+    let ctx = TestContext::new().await?;
+    let mut experiment = ctx.tracker.create_experiment("shared_params").await?;
+    let run = ctx.tracker.start_run(&mut experiment).await?;
+
+    // Copies of the run racing to store the same value all succeed.
+    let database = ctx.tracker.database();
+    let writers: Vec<_> = (0..8)
+        .map(|_| {
+            let database = database.clone();
+            let mut copy = run.clone();
+            tokio::spawn(async move {
+                copy.set_param("seed", 7)?;
+                copy.set_param("optimizer", serde_json::json!({ "name": "adam" }))?;
+                database.update_run(&mut copy).await
+            })
+        })
+        .collect();
+    for writer in writers {
+        writer.await.unwrap()?;
+    }
+
+    let mut conflicting = run.clone();
+    conflicting.set_param("seed", 8)?;
+    assert!(matches!(
+        ctx.tracker.save_run(&mut conflicting).await,
+        Err(TrackerError::ParamConflict(_))
+    ));
+    let stored = ctx.tracker.get_run(run.id).await?.unwrap();
+    assert_eq!(stored.params["seed"], 7);
+    assert_eq!(stored.params["optimizer.name"], "adam");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_compare_runs() -> Result<()> {
+    let ctx = TestContext::new().await?;
+    let mut experiment = ctx.tracker.create_experiment("compare").await?;
+
+    let mut ids = Vec::new();
+    for (lr, losses, accuracy, weights, checkpoint) in [
+        (0.01, [0.9, 0.3, 0.4], 0.8, b"a".as_slice(), "a/model.pt"),
+        (0.1, [0.8, 0.5, 0.2], 0.7, b"b".as_slice(), "b/model.pt"),
+    ] {
+        let run = ctx.tracker.start_active_run(&mut experiment).await?;
+        run.log_params([("lr", serde_json::json!(lr)), ("batch_size", 32.into())])
+            .await?;
+        run.set_tag("owner", "me").await?;
+        for loss in losses {
+            run.log_metric("loss", loss).await?;
+        }
+        run.log_metric("accuracy", accuracy).await?;
+        run.log_artifact("config", "config.json", b"{}").await?;
+        run.log_artifact("model", "model.bin", weights).await?;
+        run.log_artifact("checkpoint", checkpoint, b"same").await?;
+        run.finish().await?;
+        ids.push(run.id());
+    }
+    let extra = ctx.tracker.start_active_run(&mut experiment).await?;
+    extra.set_tag("owner", "you").await?;
+    extra.finish().await?;
+
+    let comparison = ctx.tracker.compare_runs(&ids).await?;
+    assert_eq!(comparison.run_ids, ids);
+    assert_eq!(
+        comparison.params.keys().collect::<Vec<_>>(),
+        ["lr"],
+        "only differing params are listed"
+    );
+    assert_eq!(
+        comparison.params["lr"],
+        vec![Some(serde_json::json!(0.01)), Some(serde_json::json!(0.1))]
+    );
+    assert!(comparison.tags.is_empty());
+    // Artifacts are keyed by their path within the run, so checkpoints in
+    // different directories do not collide.
+    assert_eq!(
+        comparison.artifacts.keys().collect::<Vec<_>>(),
+        ["a/model.pt", "b/model.pt", "model.bin"]
+    );
+    let same = blake3::hash(b"same").to_hex().to_string();
+    assert_eq!(comparison.artifacts["a/model.pt"], vec![Some(same), None]);
+
+    let artifacts = ctx.tracker.list_artifacts(ids[1]).await?;
+    let file_names: Vec<_> = artifacts.iter().map(|a| a.file_name.as_str()).collect();
+    assert_eq!(file_names, ["b/model.pt", "config.json", "model.bin"]);
+    assert_eq!(artifacts[2].name, "model");
+    assert_eq!(artifacts[2].metadata.size_bytes, 1);
+    assert_eq!(
+        artifacts[2].metadata.content_hash,
+        blake3::hash(b"b").to_hex().to_string()
+    );
+
+    let loss = &comparison.metrics["loss"];
+    assert_eq!(loss.goal, ml_tracker::MetricGoal::Minimize);
+    assert_eq!(loss.last, vec![Some(0.4), Some(0.2)]);
+    assert_eq!(loss.best, vec![Some(0.3), Some(0.2)]);
+    assert_eq!(loss.best_run, Some(1));
+    let accuracy = &comparison.metrics["accuracy"];
+    assert_eq!(accuracy.goal, ml_tracker::MetricGoal::Maximize);
+    assert_eq!(accuracy.best_run, Some(0));
+
+    let with_extra = ctx.tracker.compare_runs(&[ids[0], extra.id()]).await?;
+    assert_eq!(
+        with_extra.tags["owner"],
+        vec![Some("me".to_string()), Some("you".to_string())]
+    );
+    assert_eq!(with_extra.metrics["loss"].last, vec![Some(0.4), None]);
+    assert_eq!(with_extra.metrics["loss"].best_run, Some(0));
+
+    assert!(ctx.tracker.compare_runs(&[]).await.is_err());
+    assert!(matches!(
+        ctx.tracker.compare_runs(&[uuid::Uuid::new_v4()]).await,
+        Err(TrackerError::NotFound(_))
+    ));
 
     Ok(())
 }
@@ -0,0 +1,395 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use tempfile::TempDir;
+
+/// Runs the `ml-tracker` binary against a database and artifact directory
+/// inside `dir`, isolated from any `ML_TRACKER_*` variables in the
+/// environment running the tests.
+fn ml_tracker(dir: &TempDir) -> Command {
+    let mut cmd = Command::cargo_bin("ml-tracker").unwrap();
+    cmd.env_remove("ML_TRACKER_CONFIG")
+        .env_remove("ML_TRACKER_STORAGE_PATH")
+        .env(
+            "ML_TRACKER_DATABASE_URL",
+            format!("sqlite:{}", dir.path().join("experiments.db").display()),
+        )
+        .arg("--storage-path")
+        .arg(dir.path().join("artifacts"));
+    cmd
+}
+
+fn created_id(output: &[u8]) -> String {
+    let stdout = String::from_utf8_lossy(output);
+    let start = stdout.find("(ID: ").expect("id in output") + "(ID: ".len();
+    stdout[start..start + 36].to_string()
+}
+
+#[test]
+fn test_create_and_list_experiments() {
+    let dir = tempfile::tempdir().unwrap();
+
+    ml_tracker(&dir)
+        .args([
+            "create-experiment",
+            "--name",
+            "resnet",
+            "-d",
+            "baseline sweep",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Created experiment resnet"));
+
+    ml_tracker(&dir)
+        .arg("list-experiments")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("resnet").and(predicate::str::contains("baseline sweep")));
+}
+
+#[test]
+fn test_show_run_and_artifacts_for_unknown_run() {
+    let dir = tempfile::tempdir().unwrap();
+    let run_id = "00000000-0000-0000-0000-000000000000";
+
+    ml_tracker(&dir)
+        .args(["show-run", "--run-id", run_id])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Not found"));
+
+    ml_tracker(&dir)
+        .args(["list-artifacts", "--run-id", run_id])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No artifacts found"));
+}
+
+#[test]
+fn test_config_file_is_loaded() {
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("ml-tracker.toml");
+    std::fs::write(
+        &config_path,
+        format!(
+            "database_url = \"sqlite:{}\"\n",
+            dir.path().join("from-file.db").display()
+        ),
+    )
+    .unwrap();
+
+    let output = Command::cargo_bin("ml-tracker")
+        .unwrap()
+        .env_remove("ML_TRACKER_DATABASE_URL")
+        .env_remove("ML_TRACKER_STORAGE_PATH")
+        .env("ML_TRACKER_CONFIG", &config_path)
+        .args(["create-experiment", "--name", "from_file"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    assert_eq!(created_id(&output).len(), 36);
+    assert!(dir.path().join("from-file.db").exists());
+
+    std::fs::write(&config_path, "unknown_key = 1\n").unwrap();
+    Command::cargo_bin("ml-tracker")
+        .unwrap()
+        .args([
+            "--config",
+            config_path.to_str().unwrap(),
+            "list-experiments",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid configuration"));
+}
+
+#[test]
+fn test_search_runs_rejects_bad_filter() {
+    let dir = tempfile::tempdir().unwrap();
+
+    ml_tracker(&dir)
+        .args(["search-runs", "--filter", "metrics.loss <"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid query"));
+
+    ml_tracker(&dir)
+        .args(["search-runs", "--filter", "status = Completed"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("0 run(s) matched"));
+}
+
+#[test]
+fn test_start_run_without_a_terminal() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = ml_tracker(&dir)
+        .args(["create-experiment", "--name", "sweep"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let experiment_id = created_id(&output);
+
+    let params_file = dir.path().join("hparams.json");
+    std::fs::write(
+        &params_file,
+        r#"{"lr": "0.1", "batch_size": 32, "optimizer": {"momentum": 0.9}}"#,
+    )
+    .unwrap();
+
+    let output = ml_tracker(&dir)
+        .args(["start-run", "--experiment-id", &experiment_id])
+        .args(["--param", "lr=0.01", "--tag", "owner=me"])
+        .arg("--params-file")
+        .arg(&params_file)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let run_id = String::from_utf8(output).unwrap().trim().to_string();
+    assert_eq!(run_id.len(), 36);
+
+    ml_tracker(&dir)
+        .args(["show-run", "--run-id", &run_id])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match(r"lr\s+0\.01\n")
+                .unwrap()
+                .and(predicate::str::is_match(r"batch_size\s+32\n").unwrap())
+                .and(predicate::str::is_match(r"optimizer\.momentum\s+0\.9\n").unwrap())
+                .and(predicate::str::is_match(r"owner\s+me\n").unwrap())
+                .and(predicate::str::contains("Running")),
+        );
+
+    // Params keep their JSON types, so they compare numerically.
+    ml_tracker(&dir)
+        .args(["search-runs", "--filter"])
+        .arg("params.lr < 0.05 and params.optimizer.momentum >= 0.9")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(run_id.as_str()));
+
+    // With stdin not a terminal and no flags, the run starts without prompting.
+    ml_tracker(&dir)
+        .args(["start-run", "--experiment-id", &experiment_id])
+        .write_stdin("")
+        .assert()
+        .success();
+
+    ml_tracker(&dir)
+        .args([
+            "start-run",
+            "--experiment-id",
+            &experiment_id,
+            "--param",
+            "lr",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("expected KEY=VALUE"));
+}
+
+#[test]
+fn test_compare_runs() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = ml_tracker(&dir)
+        .args(["create-experiment", "--name", "sweep"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let experiment_id = created_id(&output);
+
+    let start = |lr: &str| {
+        let output = ml_tracker(&dir)
+            .args(["start-run", "--experiment-id", &experiment_id])
+            .args(["--param", &format!("lr={}", lr), "--param", "batch_size=32"])
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        String::from_utf8(output).unwrap().trim().to_string()
+    };
+    let first = start("0.01");
+    let second = start("0.1");
+
+    ml_tracker(&dir)
+        .args(["compare", "--run-id", &first, "--run-id", &second])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match(r"lr\s+0\.01\s+0\.1\n")
+                .unwrap()
+                .and(predicate::str::contains("batch_size").not()),
+        );
+
+    ml_tracker(&dir)
+        .args([
+            "compare",
+            "--run-id",
+            &first,
+            &uuid::Uuid::new_v4().to_string(),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Not found"));
+}
+
+#[test]
+fn test_plot_metric() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = ml_tracker::Config::default()
+        .with_database_url(format!(
+            "sqlite:{}",
+            dir.path().join("experiments.db").display()
+        ))
+        .with_storage_path(dir.path().join("artifacts"));
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let run_ids = runtime.block_on(async {
+        let tracker = ml_tracker::ExperimentTracker::new(config).await.unwrap();
+        let mut experiment = tracker.create_experiment("curves").await.unwrap();
+        let mut run_ids = Vec::new();
+        for scale in [1.0, 2.0] {
+            let run = tracker.start_active_run(&mut experiment).await.unwrap();
+            for step in 0..500u64 {
+                let loss = scale / (step + 1) as f64;
+                run.log_metric_at_step("loss", loss, step).await.unwrap();
+            }
+            run.finish().await.unwrap();
+            run_ids.push(run.id().to_string());
+        }
+        tracker.shutdown().await.unwrap();
+        run_ids
+    });
+
+    ml_tracker(&dir)
+        .args(["plot", "--metric", "loss", "--width", "40", "--height", "8"])
+        .args(["--run-id", &run_ids[0], "--run-id", &run_ids[1]])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("2 ┤")
+                .and(predicate::str::contains("└".to_string() + &"─".repeat(40)))
+                .and(predicate::str::contains("499"))
+                .and(predicate::str::contains(format!("#1 {}", run_ids[0])))
+                .and(predicate::str::contains(format!("#2 {}", run_ids[1])))
+                .and(predicate::str::is_match("[\u{2801}-\u{28ff}]").unwrap()),
+        );
+
+    ml_tracker(&dir)
+        .args(["plot", "--metric", "accuracy", "--run-id", &run_ids[0]])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "No stepped points of metric 'accuracy'",
+        ));
+
+    let missing = uuid::Uuid::new_v4().to_string();
+    ml_tracker(&dir)
+        .args(["plot", "--metric", "loss", "--run-id", &missing])
+        .assert()
+        .failure()
+        .stderr(
+            predicate::str::contains(format!("Not found: Run {}", missing))
+                .and(predicate::str::contains("not found").not()),
+        );
+}
+
+#[test]
+fn test_list_artifacts_uses_recorded_metadata() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = ml_tracker::Config::default()
+        .with_database_url(format!(
+            "sqlite:{}",
+            dir.path().join("experiments.db").display()
+        ))
+        .with_storage_path(dir.path().join("artifacts"));
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let run_id = runtime.block_on(async {
+        let tracker = ml_tracker::ExperimentTracker::new(config).await.unwrap();
+        let mut experiment = tracker.create_experiment("artifacts").await.unwrap();
+        let run = tracker.start_active_run(&mut experiment).await.unwrap();
+        run.log_artifact("checkpoint", "ckpt/model.pt", b"weights")
+            .await
+            .unwrap();
+        // Written straight to storage, so it has no recorded metadata.
+        ml_tracker::Storage::store_artifact(
+            &ml_tracker::LocalStorage::new(dir.path().join("artifacts")),
+            run.id(),
+            "notes.txt",
+            b"hello",
+        )
+        .await
+        .unwrap();
+        run.finish().await.unwrap();
+        tracker.shutdown().await.unwrap();
+        run.id().to_string()
+    });
+
+    let hash = blake3::hash(b"weights").to_hex().to_string();
+    ml_tracker(&dir)
+        .args(["list-artifacts", "--run-id", &run_id])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match(format!(r"ckpt/model\.pt\s+7\s+{}\n", hash))
+                .unwrap()
+                .and(predicate::str::is_match(r"notes\.txt\s+5\s+-\n").unwrap()),
+        );
+}
+
+#[test]
+fn test_compare_tolerates_unusual_artifact_hashes() {
+    let dir = tempfile::tempdir().unwrap();
+    let database_url = format!("sqlite:{}", dir.path().join("experiments.db").display());
+    let config = ml_tracker::Config::default()
+        .with_database_url(&database_url)
+        .with_storage_path(dir.path().join("artifacts"));
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let run_ids = runtime.block_on(async {
+        let tracker = ml_tracker::ExperimentTracker::new(config).await.unwrap();
+        let mut experiment = tracker.create_experiment("hashes").await.unwrap();
+        let mut run_ids = Vec::new();
+        for weights in [&b"old"[..], b"new"] {
+            let run = tracker.start_active_run(&mut experiment).await.unwrap();
+            run.log_artifact("model", "model.pt", weights)
+                .await
+                .unwrap();
+            run.finish().await.unwrap();
+            run_ids.push(run.id());
+        }
+
+        tracker.shutdown().await.unwrap();
+
+        // Recorded hashes are plain text and need not be BLAKE3 hex.
+        let pool = sqlx::SqlitePool::connect(&database_url).await.unwrap();
+        for (run_id, hash) in run_ids.iter().zip(["abc", "aéééééé"]) {
+            sqlx::query("UPDATE artifacts SET content_hash = ? WHERE run_id = ?")
+                .bind(hash)
+                .bind(run_id)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        pool.close().await;
+        run_ids
+    });
+
+    ml_tracker(&dir)
+        .args(["compare", "--run-id", &run_ids[0].to_string()])
+        .args(["--run-id", &run_ids[1].to_string()])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"model\.pt\s+abc\s+aéééééé\n").unwrap());
+}
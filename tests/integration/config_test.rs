@@ -0,0 +1,50 @@
+//! Config layering reads the process environment, so these tests get a test
+//! binary of their own: no other test can observe the `ML_TRACKER_*`
+//! variables they set, and the single test setting them never races itself.
+
+use ml_tracker::{Config, Result, TrackerError};
+use std::time::Duration;
+
+#[test]
+fn test_config_layers() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("ml-tracker.toml");
+    std::fs::write(
+        &path,
+        "database_url = \"sqlite:from-file.db\"\ndb_pool_size = 8\nmetric_flush_interval_secs = 5\n",
+    )?;
+
+    let config = Config::from_file(&path)?;
+    assert_eq!(config.database_url, "sqlite:from-file.db");
+    assert_eq!(config.metric_flush_interval, Duration::from_secs(5));
+    assert_eq!(
+        config.metric_buffer_size,
+        Config::default().metric_buffer_size
+    );
+
+    std::env::set_var("ML_TRACKER_DB_POOL_SIZE", "3");
+    let layered = Config::default()
+        .with_storage_path("/tmp/artifacts")
+        .merge_file(&path)
+        .and_then(Config::merge_env);
+    std::env::set_var("ML_TRACKER_DB_POOL_SIZE", "many");
+    let invalid = Config::from_env();
+    std::env::remove_var("ML_TRACKER_DB_POOL_SIZE");
+
+    let layered = layered?;
+    assert_eq!(layered.db_pool_size, 3);
+    assert_eq!(layered.database_url, "sqlite:from-file.db");
+    assert_eq!(
+        layered.storage_path,
+        std::path::PathBuf::from("/tmp/artifacts")
+    );
+    assert!(matches!(invalid, Err(TrackerError::InvalidConfig(_))));
+
+    std::fs::write(&path, "database = \"typo\"\n")?;
+    assert!(matches!(
+        Config::from_file(&path),
+        Err(TrackerError::InvalidConfig(_))
+    ));
+
+    Ok(())
+}